@@ -0,0 +1,65 @@
+//! Compares copying encoded frames into fresh allocations versus a [BufferPool].
+//!
+//! Run natively with `cargo bench -p web-codecs --target x86_64-unknown-linux-gnu`.
+//! This only measures the allocation strategy used by [web_codecs::EncodedPayload::bytes_pooled];
+//! copying out of JS can't be measured outside of a browser, so the decode path is compared by the `bench` module
+//! in `src/payload.rs` instead, run with `wasm-pack test --headless --chrome web-codecs`.
+use std::{
+	collections::VecDeque,
+	hint::black_box,
+	time::{Duration, Instant},
+};
+
+use bytes::{Bytes, BytesMut};
+use web_codecs::BufferPool;
+
+// Roughly a 4K keyframe and delta frame at 20 Mb/s.
+const SIZES: [usize; 2] = [1_000_000, 80_000];
+
+// The number of frames alive at once, ex. waiting in a jitter buffer.
+const IN_FLIGHT: usize = 8;
+
+const FRAMES: usize = 10_000;
+
+fn run(name: &str, mut alloc: impl FnMut(&[u8]) -> Bytes) -> Duration {
+	let sources: Vec<Vec<u8>> = SIZES.iter().map(|size| vec![0xAB; *size]).collect();
+	let mut in_flight = VecDeque::with_capacity(IN_FLIGHT);
+
+	let start = Instant::now();
+
+	for i in 0..FRAMES {
+		// One keyframe every 30 frames.
+		let source = &sources[(i % 30 != 0) as usize];
+		let bytes = alloc(black_box(source));
+
+		if in_flight.len() == IN_FLIGHT {
+			in_flight.pop_front();
+		}
+		in_flight.push_back(black_box(bytes));
+	}
+
+	let elapsed = start.elapsed();
+	println!("{name}: {:?} per frame", elapsed / FRAMES as u32);
+
+	elapsed
+}
+
+fn main() {
+	// The previous behavior: allocate, zero, then copy.
+	let fresh = run("fresh", |source| {
+		let mut buffer = BytesMut::with_capacity(source.len());
+		buffer.resize(source.len(), 0);
+		buffer.copy_from_slice(source);
+		buffer.freeze()
+	});
+
+	let mut pool = BufferPool::new(IN_FLIGHT * 2);
+	let pooled = run("pooled", |source| pool.copy_from_slice(source));
+
+	println!(
+		"pooled: {} allocations, {} reused, {:.2}x faster",
+		pool.allocated(),
+		pool.reused(),
+		fresh.as_secs_f64() / pooled.as_secs_f64()
+	);
+}
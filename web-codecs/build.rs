@@ -0,0 +1,9 @@
+fn main() {
+	println!("cargo::rustc-check-cfg=cfg(mock)");
+
+	// The mock backend only replaces WebCodecs natively, so wasm32 builds always check the real one.
+	let wasm = std::env::var("CARGO_CFG_TARGET_ARCH").is_ok_and(|arch| arch == "wasm32");
+	if std::env::var_os("CARGO_FEATURE_MOCK").is_some() && !wasm {
+		println!("cargo::rustc-cfg=mock");
+	}
+}
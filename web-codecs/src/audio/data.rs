@@ -1,15 +1,25 @@
 use std::ops::{Deref, DerefMut};
 use std::time::Duration;
 
-use crate::{Error, Result, Timestamp};
+use crate::{Result, Timestamp};
+
+#[cfg(not(mock))]
+use crate::Error;
 
 pub use web_sys::AudioSampleFormat as AudioDataFormat;
 
+#[cfg(not(mock))]
+type AudioDataInner = web_sys::AudioData;
+
+#[cfg(mock)]
+type AudioDataInner = crate::mock::AudioData;
+
 /// A wrapper around [web_sys::AudioData] that closes on Drop.
 // It's an option so `leak` can return the inner AudioData if needed.
 #[derive(Debug)]
-pub struct AudioData(Option<web_sys::AudioData>);
+pub struct AudioData(Option<AudioDataInner>);
 
+#[cfg(not(mock))]
 impl AudioData {
 	/// A helper to construct AudioData in a more type-safe way.
 	/// This currently only supports F32.
@@ -36,7 +46,7 @@ impl AudioData {
 			// Copy the other channels using a Float32Array as a view into the buffer.
 			let slice = js_sys::Float32Array::new_with_byte_offset_and_length(
 				&data.buffer(),
-				(i * frame_count) as u32,
+				(i * frame_count * size_of::<f32>()) as u32,
 				frame_count as _,
 			);
 			slice.copy_from(channel);
@@ -62,6 +72,55 @@ impl AudioData {
 		Ok(Self(Some(audio_data)))
 	}
 
+	pub fn leak(mut self) -> web_sys::AudioData {
+		self.0.take().unwrap()
+	}
+
+	fn copy_plane(
+		&self,
+		dst: &mut [u8],
+		channel: usize,
+		options: AudioCopyOptions,
+		format: Option<AudioDataFormat>,
+	) -> Result<()> {
+		let options = options.into_web_sys(channel);
+		if let Some(format) = format {
+			options.set_format(format);
+		}
+
+		self.0.as_ref().unwrap().copy_to_with_u8_slice(dst, &options)?;
+		Ok(())
+	}
+}
+
+#[cfg(mock)]
+impl AudioData {
+	/// A helper to construct AudioData in a more type-safe way.
+	/// This currently only supports F32.
+	pub fn new<'a>(
+		channels: impl ExactSizeIterator<Item = &'a [f32]>,
+		sample_rate: u32,
+		timestamp: Timestamp,
+	) -> Result<Self> {
+		let audio_data = crate::mock::AudioData::new(channels, sample_rate, timestamp)?;
+		Ok(Self(Some(audio_data)))
+	}
+
+	fn copy_plane(
+		&self,
+		dst: &mut [u8],
+		channel: usize,
+		options: AudioCopyOptions,
+		format: Option<AudioDataFormat>,
+	) -> Result<()> {
+		self.0
+			.as_ref()
+			.unwrap()
+			.copy_to(dst, channel, options.offset, options.count, format)
+	}
+}
+
+impl AudioData {
 	pub fn timestamp(&self) -> Timestamp {
 		Timestamp::from_micros(self.0.as_ref().unwrap().timestamp() as _)
 	}
@@ -74,16 +133,29 @@ impl AudioData {
 		self.0.as_ref().unwrap().sample_rate() as u32
 	}
 
-	pub fn append_to<T: AudioAppend>(&self, dst: &mut T, channel: usize, options: AudioCopyOptions) -> Result<()> {
+	pub fn append_to<T: AudioAppend + ?Sized>(
+		&self,
+		dst: &mut T,
+		channel: usize,
+		options: AudioCopyOptions,
+	) -> Result<()> {
 		dst.append_to(self, channel, options)
 	}
 
-	pub fn copy_to<T: AudioCopy>(&self, dst: &mut T, channel: usize, options: AudioCopyOptions) -> Result<()> {
+	pub fn copy_to<T: AudioCopy + ?Sized>(&self, dst: &mut T, channel: usize, options: AudioCopyOptions) -> Result<()> {
 		dst.copy_to(self, channel, options)
 	}
 
-	pub fn leak(mut self) -> web_sys::AudioData {
-		self.0.take().unwrap()
+	/// Copy each channel into a separate planar f32 buffer.
+	pub fn to_planes(&self) -> Result<Vec<Vec<f32>>> {
+		let frames = self.number_of_frames() as usize;
+		let mut planes = vec![vec![0.0; frames]; self.number_of_channels() as usize];
+
+		for (channel, plane) in planes.iter_mut().enumerate() {
+			self.copy_to(plane.as_mut_slice(), channel, Default::default())?;
+		}
+
+		Ok(planes)
 	}
 }
 
@@ -94,7 +166,7 @@ impl Clone for AudioData {
 }
 
 impl Deref for AudioData {
-	type Target = web_sys::AudioData;
+	type Target = AudioDataInner;
 
 	fn deref(&self) -> &Self::Target {
 		self.0.as_ref().unwrap()
@@ -116,8 +188,8 @@ impl Drop for AudioData {
 	}
 }
 
-impl From<web_sys::AudioData> for AudioData {
-	fn from(this: web_sys::AudioData) -> Self {
+impl From<AudioDataInner> for AudioData {
+	fn from(this: AudioDataInner) -> Self {
 		Self(Some(this))
 	}
 }
@@ -128,26 +200,21 @@ pub trait AudioCopy {
 
 impl AudioCopy for [u8] {
 	fn copy_to(&mut self, data: &AudioData, channel: usize, options: AudioCopyOptions) -> Result<()> {
-		let options = options.into_web_sys(channel);
 		// NOTE: The format is unuset so it will default to the AudioData format.
 		// This means you couldn't export as U8Planar for whatever that's worth...
-		data.0.as_ref().unwrap().copy_to_with_u8_slice(self, &options)?;
-		Ok(())
+		data.copy_plane(self, channel, options, None)
 	}
 }
 
 impl AudioCopy for [f32] {
 	fn copy_to(&mut self, data: &AudioData, channel: usize, options: AudioCopyOptions) -> Result<()> {
-		let options = options.into_web_sys(channel);
-		options.set_format(AudioDataFormat::F32Planar);
-
 		// Cast from a f32 to a u8 slice.
 		let bytes = bytemuck::cast_slice_mut(self);
-		data.0.as_ref().unwrap().copy_to_with_u8_slice(bytes, &options)?;
-		Ok(())
+		data.copy_plane(bytes, channel, options, Some(AudioDataFormat::F32Planar))
 	}
 }
 
+#[cfg(not(mock))]
 impl AudioCopy for js_sys::Uint8Array {
 	fn copy_to(&mut self, data: &AudioData, channel: usize, options: AudioCopyOptions) -> Result<()> {
 		let options = options.into_web_sys(channel);
@@ -156,6 +223,7 @@ impl AudioCopy for js_sys::Uint8Array {
 	}
 }
 
+#[cfg(not(mock))]
 impl AudioCopy for js_sys::Float32Array {
 	fn copy_to(&mut self, data: &AudioData, channel: usize, options: AudioCopyOptions) -> Result<()> {
 		let options = options.into_web_sys(channel);
@@ -175,11 +243,8 @@ impl AudioAppend for Vec<f32> {
 		let offset = self.len();
 		self.resize(offset + grow, 0.0);
 
-		let options = options.into_web_sys(channel);
 		let bytes = bytemuck::cast_slice_mut(&mut self[offset..]);
-		data.0.as_ref().unwrap().copy_to_with_u8_slice(bytes, &options)?;
-
-		Ok(())
+		data.copy_plane(bytes, channel, options, Some(AudioDataFormat::F32Planar))
 	}
 }
 
@@ -190,6 +255,7 @@ pub struct AudioCopyOptions {
 }
 
 impl AudioCopyOptions {
+	#[cfg(not(mock))]
 	fn into_web_sys(self, channel: usize) -> web_sys::AudioDataCopyToOptions {
 		let options = web_sys::AudioDataCopyToOptions::new(channel as _);
 		options.set_frame_offset(self.offset as _);
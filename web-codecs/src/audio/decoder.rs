@@ -1,5 +1,6 @@
 use bytes::{Bytes, BytesMut};
 use tokio::sync::{mpsc, watch};
+#[cfg(not(mock))]
 use wasm_bindgen::prelude::*;
 
 use super::AudioData;
@@ -32,6 +33,12 @@ impl AudioDecoderConfig {
 
 	/// Check if the configuration is supported by this browser.
 	/// Returns an error if the configuration is invalid, and false if just unsupported.
+	#[cfg(mock)]
+	pub async fn is_supported(&self) -> Result<bool, Error> {
+		Ok(true)
+	}
+
+	#[cfg(not(mock))]
 	pub async fn is_supported(&self) -> Result<bool, Error> {
 		let res =
 			wasm_bindgen_futures::JsFuture::from(web_sys::AudioDecoder::is_config_supported(&self.into())).await?;
@@ -47,31 +54,8 @@ impl AudioDecoderConfig {
 	pub fn build(self) -> Result<(AudioDecoder, AudioDecoded), Error> {
 		let (frames_tx, frames_rx) = mpsc::unbounded_channel();
 		let (closed_tx, closed_rx) = watch::channel(Ok(()));
-		let closed_tx2 = closed_tx.clone();
-
-		let on_error = Closure::wrap(Box::new(move |e: JsValue| {
-			closed_tx.send_replace(Err(Error::from(e))).ok();
-		}) as Box<dyn FnMut(_)>);
-
-		let on_frame = Closure::wrap(Box::new(move |e: JsValue| {
-			let frame: web_sys::AudioData = e.unchecked_into();
-			let frame = AudioData::from(frame);
-
-			if frames_tx.send(frame).is_err() {
-				closed_tx2.send_replace(Err(Error::Dropped)).ok();
-			}
-		}) as Box<dyn FnMut(_)>);
-
-		let init = web_sys::AudioDecoderInit::new(on_error.as_ref().unchecked_ref(), on_frame.as_ref().unchecked_ref());
-		let inner: web_sys::AudioDecoder = web_sys::AudioDecoder::new(&init).unwrap();
-		inner.configure(&(&self).into())?;
-
-		let decoder = AudioDecoder {
-			inner,
-			on_error,
-			on_frame,
-		};
 
+		let decoder = AudioDecoder::new(&self, frames_tx, closed_tx)?;
 		let decoded = AudioDecoded {
 			frames: frames_rx,
 			closed: closed_rx,
@@ -120,29 +104,61 @@ impl From<web_sys::AudioDecoderConfig> for AudioDecoderConfig {
 }
 
 pub struct AudioDecoder {
+	#[cfg(not(mock))]
 	inner: web_sys::AudioDecoder,
 
+	#[cfg(mock)]
+	inner: crate::mock::AudioDecoder,
+
 	// These are held to avoid dropping them.
+	#[cfg(not(mock))]
 	#[allow(dead_code)]
 	on_error: Closure<dyn FnMut(JsValue)>,
+	#[cfg(not(mock))]
 	#[allow(dead_code)]
 	on_frame: Closure<dyn FnMut(JsValue)>,
 }
 
+#[cfg(not(mock))]
 impl AudioDecoder {
+	fn new(
+		config: &AudioDecoderConfig,
+		frames: mpsc::UnboundedSender<AudioData>,
+		closed: watch::Sender<Result<(), Error>>,
+	) -> Result<Self, Error> {
+		let closed2 = closed.clone();
+
+		let on_error = Closure::wrap(Box::new(move |e: JsValue| {
+			closed.send_replace(Err(Error::from(e))).ok();
+		}) as Box<dyn FnMut(_)>);
+
+		let on_frame = Closure::wrap(Box::new(move |e: JsValue| {
+			let frame: web_sys::AudioData = e.unchecked_into();
+			let frame = AudioData::from(frame);
+
+			if frames.send(frame).is_err() {
+				closed2.send_replace(Err(Error::Dropped)).ok();
+			}
+		}) as Box<dyn FnMut(_)>);
+
+		let init = web_sys::AudioDecoderInit::new(on_error.as_ref().unchecked_ref(), on_frame.as_ref().unchecked_ref());
+		let inner: web_sys::AudioDecoder = web_sys::AudioDecoder::new(&init).unwrap();
+		inner.configure(&config.into())?;
+
+		Ok(Self {
+			inner,
+			on_error,
+			on_frame,
+		})
+	}
+
 	pub fn decode(&self, frame: EncodedFrame) -> Result<(), Error> {
 		let chunk_type = match frame.keyframe {
 			true => web_sys::EncodedAudioChunkType::Key,
 			false => web_sys::EncodedAudioChunkType::Delta,
 		};
 
-		let chunk = web_sys::EncodedAudioChunkInit::new(
-			&js_sys::Uint8Array::from(frame.payload.as_ref()),
-			frame.timestamp.as_micros() as _,
-			chunk_type,
-		);
-
-		let chunk = web_sys::EncodedAudioChunk::new(&chunk)?;
+		let chunk = frame.payload.chunk(frame.timestamp, chunk_type)?;
 		self.inner.decode(&chunk)?;
 
 		Ok(())
@@ -158,6 +174,31 @@ impl AudioDecoder {
 	}
 }
 
+#[cfg(mock)]
+impl AudioDecoder {
+	fn new(
+		_config: &AudioDecoderConfig,
+		frames: mpsc::UnboundedSender<AudioData>,
+		closed: watch::Sender<Result<(), Error>>,
+	) -> Result<Self, Error> {
+		let inner = crate::mock::AudioDecoder::new(frames, closed);
+		Ok(Self { inner })
+	}
+
+	pub fn decode(&self, frame: EncodedFrame) -> Result<(), Error> {
+		self.inner.decode(frame)
+	}
+
+	pub async fn flush(&self) -> Result<(), Error> {
+		self.inner.flush();
+		Ok(())
+	}
+
+	pub fn queue_size(&self) -> u32 {
+		0
+	}
+}
+
 impl Drop for AudioDecoder {
 	fn drop(&mut self) {
 		let _ = self.inner.close();
@@ -1,6 +1,7 @@
 use std::{cell::RefCell, rc::Rc};
 
 use tokio::sync::{mpsc, watch};
+#[cfg(not(mock))]
 use wasm_bindgen::prelude::*;
 
 use crate::{EncodedFrame, Error};
@@ -26,6 +27,12 @@ impl AudioEncoderConfig {
 		}
 	}
 
+	#[cfg(mock)]
+	pub async fn is_supported(&self) -> Result<bool, Error> {
+		Ok(true)
+	}
+
+	#[cfg(not(mock))]
 	pub async fn is_supported(&self) -> Result<bool, Error> {
 		let res =
 			wasm_bindgen_futures::JsFuture::from(web_sys::AudioEncoder::is_config_supported(&self.into())).await?;
@@ -43,7 +50,13 @@ impl AudioEncoderConfig {
 		let (closed_tx, closed_rx) = watch::channel(Ok(()));
 		let config = Rc::new(RefCell::new(None));
 
-		let decoder = AudioEncoder::new(self, config.clone(), frames_tx, closed_tx)?;
+		let output = AudioEncoderOutput {
+			config: config.clone(),
+			frames: frames_tx,
+			closed: closed_tx,
+		};
+
+		let decoder = AudioEncoder::new(self, output)?;
 		let decoded = AudioEncoded::new(config, frames_rx, closed_rx);
 
 		Ok((decoder, decoded))
@@ -70,27 +83,57 @@ impl From<&AudioEncoderConfig> for web_sys::AudioEncoderConfig {
 	}
 }
 
+/// The shared state updated whenever the encoder outputs a frame.
+#[derive(Clone)]
+pub(crate) struct AudioEncoderOutput {
+	config: Rc<RefCell<Option<AudioDecoderConfig>>>,
+	frames: mpsc::UnboundedSender<EncodedFrame>,
+	closed: watch::Sender<Result<(), Error>>,
+}
+
+impl AudioEncoderOutput {
+	pub fn frame(&self, mut frame: EncodedFrame, config: Option<AudioDecoderConfig>) {
+		frame.config_changed = config.is_some();
+
+		if let Some(config) = config {
+			self.config.borrow_mut().replace(config);
+		}
+
+		if self.frames.send(frame).is_err() {
+			self.closed.send_replace(Err(Error::Dropped)).ok();
+		}
+	}
+
+	#[cfg(not(mock))]
+	pub fn error(&self, err: Error) {
+		self.closed.send_replace(Err(err)).ok();
+	}
+}
+
 pub struct AudioEncoder {
+	#[cfg(not(mock))]
 	inner: web_sys::AudioEncoder,
+
+	#[cfg(mock)]
+	inner: crate::mock::AudioEncoder,
+
 	config: AudioEncoderConfig,
 
 	// These are held to avoid dropping them.
+	#[cfg(not(mock))]
 	#[allow(dead_code)]
 	on_error: Closure<dyn FnMut(JsValue)>,
+	#[cfg(not(mock))]
 	#[allow(dead_code)]
 	on_frame: Closure<dyn FnMut(JsValue, JsValue)>,
 }
 
+#[cfg(not(mock))]
 impl AudioEncoder {
-	fn new(
-		config: AudioEncoderConfig,
-		on_config: Rc<RefCell<Option<AudioDecoderConfig>>>,
-		on_frame: mpsc::UnboundedSender<EncodedFrame>,
-		on_error: watch::Sender<Result<(), Error>>,
-	) -> Result<Self, Error> {
-		let on_error2 = on_error.clone();
+	fn new(config: AudioEncoderConfig, output: AudioEncoderOutput) -> Result<Self, Error> {
+		let output2 = output.clone();
 		let on_error = Closure::wrap(Box::new(move |e: JsValue| {
-			on_error.send_replace(Err(Error::from(e))).ok();
+			output2.error(Error::from(e));
 		}) as Box<dyn FnMut(_)>);
 
 		let on_frame = Closure::wrap(Box::new(move |frame: JsValue, meta: JsValue| {
@@ -98,20 +141,18 @@ impl AudioEncoder {
 			let frame: web_sys::EncodedAudioChunk = frame.unchecked_into();
 			let frame = EncodedFrame::from(frame);
 
+			let mut decoder_config = None;
+
 			if let Ok(metadata) = meta.dyn_into::<js_sys::Object>() {
-				// TODO handle metadata
 				if let Ok(config) = js_sys::Reflect::get(&metadata, &"decoderConfig".into()) {
 					if !config.is_falsy() {
 						let config: web_sys::AudioDecoderConfig = config.unchecked_into();
-						let config = AudioDecoderConfig::from(config);
-						on_config.borrow_mut().replace(config);
+						decoder_config = Some(AudioDecoderConfig::from(config));
 					}
 				}
 			}
 
-			if on_frame.send(frame).is_err() {
-				on_error2.send_replace(Err(Error::Dropped)).ok();
-			}
+			output.frame(frame, decoder_config);
 		}) as Box<dyn FnMut(_, _)>);
 
 		let init = web_sys::AudioEncoderInit::new(on_error.as_ref().unchecked_ref(), on_frame.as_ref().unchecked_ref());
@@ -135,16 +176,39 @@ impl AudioEncoder {
 		self.inner.encode_queue_size()
 	}
 
-	pub fn config(&self) -> &AudioEncoderConfig {
-		&self.config
+	pub async fn flush(&mut self) -> Result<(), Error> {
+		wasm_bindgen_futures::JsFuture::from(self.inner.flush()).await?;
+		Ok(())
+	}
+}
+
+#[cfg(mock)]
+impl AudioEncoder {
+	fn new(config: AudioEncoderConfig, output: AudioEncoderOutput) -> Result<Self, Error> {
+		let inner = crate::mock::AudioEncoder::new(&config, output);
+		Ok(Self { config, inner })
+	}
+
+	pub fn encode(&mut self, frame: &AudioData) -> Result<(), Error> {
+		self.inner.encode(frame);
+		Ok(())
+	}
+
+	pub fn queue_size(&self) -> u32 {
+		0
 	}
 
 	pub async fn flush(&mut self) -> Result<(), Error> {
-		wasm_bindgen_futures::JsFuture::from(self.inner.flush()).await?;
 		Ok(())
 	}
 }
 
+impl AudioEncoder {
+	pub fn config(&self) -> &AudioEncoderConfig {
+		&self.config
+	}
+}
+
 impl Drop for AudioEncoder {
 	fn drop(&mut self) {
 		let _ = self.inner.close();
@@ -0,0 +1,233 @@
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use crate::{Error, Result};
+
+use super::AudioData;
+
+/// Converts between channel layouts by multiplying each frame by a matrix.
+///
+/// The default matrix follows the Web Audio "speakers" rules for mono, stereo, quad, and 5.1,
+/// with channels in the order L, R, C, LFE, SL, SR for 5.1 and L, R, SL, SR for quad.
+/// Any other combination copies the channels that exist in both layouts and leaves the rest silent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelMixer {
+	input_channels: usize,
+	output_channels: usize,
+
+	// Row-major, one row per output channel.
+	matrix: Vec<f32>,
+}
+
+impl ChannelMixer {
+	pub fn new(input_channels: u32, output_channels: u32) -> Result<Self> {
+		let input = input_channels as usize;
+		let output = output_channels as usize;
+
+		if input == 0 || output == 0 {
+			return Err(Error::NoChannels);
+		}
+
+		const H: f32 = 0.5;
+		const S: f32 = FRAC_1_SQRT_2;
+
+		#[rustfmt::skip]
+		let matrix = match (input, output) {
+			(1, 2) => vec![
+				1.0,
+				1.0,
+			],
+			(1, 4) => vec![
+				1.0,
+				1.0,
+				0.0,
+				0.0,
+			],
+			(1, 6) => vec![
+				0.0,
+				0.0,
+				1.0,
+				0.0,
+				0.0,
+				0.0,
+			],
+			(2, 1) => vec![
+				H, H,
+			],
+			(4, 1) => vec![
+				0.25, 0.25, 0.25, 0.25,
+			],
+			(4, 2) => vec![
+				H, 0.0, H, 0.0,
+				0.0, H, 0.0, H,
+			],
+			(6, 1) => vec![
+				S, S, 1.0, 0.0, H, H,
+			],
+			(6, 2) => vec![
+				1.0, 0.0, S, 0.0, S, 0.0,
+				0.0, 1.0, S, 0.0, 0.0, S,
+			],
+			(6, 4) => vec![
+				1.0, 0.0, S, 0.0, 0.0, 0.0,
+				0.0, 1.0, S, 0.0, 0.0, 0.0,
+				0.0, 0.0, 0.0, 0.0, 1.0, 0.0,
+				0.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+			],
+			(4, 6) => vec![
+				1.0, 0.0, 0.0, 0.0,
+				0.0, 1.0, 0.0, 0.0,
+				0.0, 0.0, 0.0, 0.0,
+				0.0, 0.0, 0.0, 0.0,
+				0.0, 0.0, 1.0, 0.0,
+				0.0, 0.0, 0.0, 1.0,
+			],
+			// Includes stereo to quad and 5.1, which only use the front left and right.
+			_ => Self::discrete(input, output),
+		};
+
+		Ok(Self {
+			input_channels: input,
+			output_channels: output,
+			matrix,
+		})
+	}
+
+	/// Use a custom matrix, row-major with one row of `input_channels` gains per output channel.
+	pub fn with_matrix(input_channels: u32, output_channels: u32, matrix: Vec<f32>) -> Result<Self> {
+		let input = input_channels as usize;
+		let output = output_channels as usize;
+
+		if input == 0 || output == 0 {
+			return Err(Error::NoChannels);
+		}
+
+		if matrix.len() != input * output {
+			return Err(Error::OutOfRange);
+		}
+
+		Ok(Self {
+			input_channels: input,
+			output_channels: output,
+			matrix,
+		})
+	}
+
+	// Copy the channels with the same index, dropping or silencing the rest.
+	fn discrete(input: usize, output: usize) -> Vec<f32> {
+		let mut matrix = vec![0.0; input * output];
+		for channel in 0..input.min(output) {
+			matrix[channel * input + channel] = 1.0;
+		}
+		matrix
+	}
+
+	pub fn input_channels(&self) -> u32 {
+		self.input_channels as u32
+	}
+
+	pub fn output_channels(&self) -> u32 {
+		self.output_channels as u32
+	}
+
+	pub fn matrix(&self) -> &[f32] {
+		&self.matrix
+	}
+
+	/// Mix planar samples, which must all have the same length.
+	pub fn mix_planes(&self, planes: &[&[f32]]) -> Result<Vec<Vec<f32>>> {
+		if planes.len() != self.input_channels {
+			return Err(Error::UnsupportedFormat);
+		}
+
+		let frames = planes[0].len();
+		if planes.iter().any(|plane| plane.len() != frames) {
+			return Err(Error::OutOfRange);
+		}
+
+		let output = self
+			.matrix
+			.chunks_exact(self.input_channels)
+			.map(|gains| {
+				let mut output = vec![0.0; frames];
+
+				for (plane, gain) in planes.iter().zip(gains) {
+					if *gain == 0.0 {
+						continue;
+					}
+
+					for (dst, src) in output.iter_mut().zip(plane.iter()) {
+						*dst += src * gain;
+					}
+				}
+
+				output
+			})
+			.collect();
+
+		Ok(output)
+	}
+
+	/// Mix the audio into a new [AudioData] with the same timestamp and sample rate.
+	pub fn mix(&self, data: &AudioData) -> Result<AudioData> {
+		let planes = data.to_planes()?;
+		let planes: Vec<&[f32]> = planes.iter().map(Vec::as_slice).collect();
+
+		let output = self.mix_planes(&planes)?;
+		AudioData::new(output.iter().map(Vec::as_slice), data.sample_rate(), data.timestamp())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn downmix() {
+		let mixer = ChannelMixer::new(2, 1).unwrap();
+		let mono = mixer.mix_planes(&[&[1.0, 0.5], &[0.0, 0.5]]).unwrap();
+		assert_eq!(mono, [[0.5, 0.5]]);
+
+		let mixer = ChannelMixer::new(6, 2).unwrap();
+		let stereo = mixer
+			.mix_planes(&[&[1.0], &[0.0], &[1.0], &[1.0], &[0.0], &[1.0]])
+			.unwrap();
+		assert_eq!(stereo, [[1.0 + FRAC_1_SQRT_2], [2.0 * FRAC_1_SQRT_2]]);
+	}
+
+	#[test]
+	fn upmix() {
+		let mixer = ChannelMixer::new(1, 6).unwrap();
+		let surround = mixer.mix_planes(&[&[0.25]]).unwrap();
+		assert_eq!(surround, [[0.0], [0.0], [0.25], [0.0], [0.0], [0.0]]);
+
+		// Unknown layouts copy matching channels.
+		let mixer = ChannelMixer::new(3, 2).unwrap();
+		let stereo = mixer.mix_planes(&[&[1.0], &[2.0], &[3.0]]).unwrap();
+		assert_eq!(stereo, [[1.0], [2.0]]);
+	}
+
+	#[test]
+	fn invalid() {
+		assert!(matches!(ChannelMixer::new(0, 2), Err(Error::NoChannels)));
+		assert!(matches!(
+			ChannelMixer::with_matrix(2, 2, vec![1.0]),
+			Err(Error::OutOfRange)
+		));
+
+		let mixer = ChannelMixer::new(2, 1).unwrap();
+		assert!(matches!(mixer.mix_planes(&[&[1.0]]), Err(Error::UnsupportedFormat)));
+		assert!(matches!(mixer.mix_planes(&[&[1.0], &[]]), Err(Error::OutOfRange)));
+	}
+
+	#[cfg(mock)]
+	#[test]
+	fn audio_data() {
+		let data = AudioData::new([&[0.5, 1.0][..]].into_iter(), 48000, crate::Timestamp::from_millis(20)).unwrap();
+
+		let stereo = ChannelMixer::new(1, 2).unwrap().mix(&data).unwrap();
+		assert_eq!(stereo.number_of_channels(), 2);
+		assert_eq!(stereo.sample_rate(), 48000);
+		assert_eq!(stereo.timestamp(), data.timestamp());
+		assert_eq!(stereo.to_planes().unwrap(), [[0.5, 1.0], [0.5, 1.0]]);
+	}
+}
@@ -1,7 +1,13 @@
 mod data;
 mod decoder;
 mod encoder;
+mod mix;
+mod resample;
+mod wav;
 
 pub use data::*;
 pub use decoder::*;
 pub use encoder::*;
+pub use mix::*;
+pub use resample::*;
+pub use wav::*;
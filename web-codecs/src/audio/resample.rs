@@ -0,0 +1,436 @@
+use std::f64::consts::PI;
+
+use crate::{Error, Result, Timestamp};
+
+use super::AudioData;
+
+// The number of zero crossings on each side of the sinc kernel.
+const SINC_ZEROS: usize = 16;
+
+// Precompute the kernel for each phase unless the ratio is irregular, ex. 44100 to 47999.
+const MAX_PHASES: u64 = 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResampleMode {
+	/// Interpolate between neighboring samples; cheap but aliases.
+	Linear,
+
+	/// A Blackman windowed sinc filter, band-limited to the lower of the two rates.
+	#[default]
+	Sinc,
+}
+
+/// Planar f32 samples with the timestamp of the first frame.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AudioPlanes {
+	pub timestamp: Timestamp,
+	pub planes: Vec<Vec<f32>>,
+}
+
+impl AudioPlanes {
+	pub fn frames(&self) -> usize {
+		self.planes.first().map(Vec::len).unwrap_or(0)
+	}
+}
+
+/// Converts audio from one sample rate to another.
+///
+/// Input is buffered across calls so chunks can be any size, and the output timestamps are computed from the
+/// number of frames produced, so they stay continuous even when the chunk durations aren't a whole number of
+/// output frames. The input is assumed to be contiguous; call [Self::flush] or [Self::reset] after a gap.
+#[derive(Debug, Clone)]
+pub struct Resampler {
+	channels: usize,
+	input_rate: u32,
+	output_rate: u32,
+
+	// The ratio reduced to lowest terms; output frame N is at input position N * step / phases.
+	step: u64,
+	phases: u64,
+
+	// The number of taps on each side of the kernel, and the precomputed weights for each phase.
+	half: usize,
+	mode: ResampleMode,
+	cutoff: f64,
+	table: Option<Vec<f32>>,
+
+	// The input samples still needed, per channel, starting at input position `offset`.
+	// The offset is negative at the start because the stream is padded with silence.
+	buffer: Vec<Vec<f32>>,
+	offset: i64,
+
+	// Set on the first input, used to compute all output timestamps.
+	start: Option<Timestamp>,
+	input_frames: u64,
+	output_frames: u64,
+}
+
+impl Resampler {
+	pub fn new(channels: u32, input_rate: u32, output_rate: u32, mode: ResampleMode) -> Result<Self> {
+		if channels == 0 {
+			return Err(Error::NoChannels);
+		}
+
+		if input_rate == 0 || output_rate == 0 {
+			return Err(Error::OutOfRange);
+		}
+
+		let gcd = gcd(input_rate as u64, output_rate as u64);
+		let step = input_rate as u64 / gcd;
+		let phases = output_rate as u64 / gcd;
+
+		// Filter out anything above the lower Nyquist frequency when downsampling.
+		let cutoff = (output_rate as f64 / input_rate as f64).min(1.0);
+
+		let half = match mode {
+			ResampleMode::Linear => 1,
+			ResampleMode::Sinc => (SINC_ZEROS as f64 / cutoff).ceil() as usize,
+		};
+
+		let mut this = Self {
+			channels: channels as usize,
+			input_rate,
+			output_rate,
+			step,
+			phases,
+			half,
+			mode,
+			cutoff,
+			table: None,
+			buffer: Vec::new(),
+			offset: 0,
+			start: None,
+			input_frames: 0,
+			output_frames: 0,
+		};
+
+		if phases <= MAX_PHASES {
+			let mut table = vec![0.0; phases as usize * 2 * half];
+			for (phase, weights) in table.chunks_exact_mut(2 * half).enumerate() {
+				this.weights(phase as u64, weights);
+			}
+			this.table = Some(table);
+		}
+
+		this.reset();
+		Ok(this)
+	}
+
+	pub fn channels(&self) -> u32 {
+		self.channels as u32
+	}
+
+	pub fn input_rate(&self) -> u32 {
+		self.input_rate
+	}
+
+	pub fn output_rate(&self) -> u32 {
+		self.output_rate
+	}
+
+	pub fn mode(&self) -> ResampleMode {
+		self.mode
+	}
+
+	/// Resample the audio, returning None if not enough input has been buffered to produce a frame.
+	pub fn resample(&mut self, data: &AudioData) -> Result<Option<AudioData>> {
+		if data.sample_rate() != self.input_rate {
+			return Err(Error::UnsupportedFormat);
+		}
+
+		let planes = data.to_planes()?;
+		let planes: Vec<&[f32]> = planes.iter().map(Vec::as_slice).collect();
+
+		let output = self.resample_planes(&planes, data.timestamp())?;
+		self.audio_data(output)
+	}
+
+	/// Resample planar samples, which must all have the same length.
+	///
+	/// The timestamp is only used for the first chunk after creating or resetting the resampler.
+	pub fn resample_planes(&mut self, planes: &[&[f32]], timestamp: Timestamp) -> Result<AudioPlanes> {
+		if planes.len() != self.channels {
+			return Err(Error::UnsupportedFormat);
+		}
+
+		let frames = planes[0].len();
+		if planes.iter().any(|plane| plane.len() != frames) {
+			return Err(Error::OutOfRange);
+		}
+
+		self.start.get_or_insert(timestamp);
+
+		for (buffer, plane) in self.buffer.iter_mut().zip(planes) {
+			buffer.extend_from_slice(plane);
+		}
+		self.input_frames += frames as u64;
+
+		// Wait until the kernel has all of the samples it needs.
+		let available = self.input_frames.saturating_sub(self.half as u64);
+		Ok(self.process(available))
+	}
+
+	/// Output any remaining frames by padding the input with silence, then reset.
+	pub fn flush(&mut self) -> Result<Option<AudioData>> {
+		let output = self.flush_planes();
+		self.audio_data(output)
+	}
+
+	/// Like [Self::flush] but returns planar samples.
+	pub fn flush_planes(&mut self) -> AudioPlanes {
+		for buffer in self.buffer.iter_mut() {
+			buffer.resize(buffer.len() + self.half, 0.0);
+		}
+
+		let output = self.process(self.input_frames);
+		self.reset();
+
+		output
+	}
+
+	/// Discard any buffered input and start a new stream.
+	pub fn reset(&mut self) {
+		// Pad the start with silence so the first output frame lines up with the first input frame.
+		self.buffer = vec![vec![0.0; self.half - 1]; self.channels];
+		self.offset = 1 - self.half as i64;
+
+		self.start = None;
+		self.input_frames = 0;
+		self.output_frames = 0;
+	}
+
+	// Produce every output frame located before the given input position.
+	fn process(&mut self, end: u64) -> AudioPlanes {
+		let timestamp = self.timestamp(self.output_frames);
+
+		let taps = 2 * self.half;
+		let mut scratch = vec![0.0; taps];
+		let mut output = vec![Vec::new(); self.channels];
+
+		loop {
+			let position = self.output_frames * self.step;
+			let index = position / self.phases;
+			if index >= end {
+				break;
+			}
+
+			let phase = position % self.phases;
+			let weights = match &self.table {
+				Some(table) => &table[phase as usize * taps..][..taps],
+				None => {
+					self.weights(phase, &mut scratch);
+					&scratch
+				}
+			};
+
+			// The first tap is at input position index - half + 1.
+			let first = (index as i64 - self.half as i64 + 1 - self.offset) as usize;
+
+			for (output, buffer) in output.iter_mut().zip(&self.buffer) {
+				let samples = &buffer[first..first + taps];
+				let sample = samples.iter().zip(weights).map(|(s, w)| s * w).sum();
+				output.push(sample);
+			}
+
+			self.output_frames += 1;
+		}
+
+		// Drop the samples that won't be needed by the next output frame.
+		let index = (self.output_frames * self.step / self.phases) as i64;
+		let unused = (index - self.half as i64 + 1 - self.offset).max(0) as usize;
+
+		// When downsampling, the next frame may need samples that haven't arrived yet.
+		let unused = unused.min(self.buffer[0].len());
+
+		for buffer in self.buffer.iter_mut() {
+			buffer.drain(..unused);
+		}
+		self.offset += unused as i64;
+
+		AudioPlanes {
+			timestamp,
+			planes: output,
+		}
+	}
+
+	// Compute the kernel for an output frame that lies phase/phases after an input frame.
+	fn weights(&self, phase: u64, weights: &mut [f32]) {
+		let fraction = phase as f64 / self.phases as f64;
+		let half = self.half as f64;
+
+		for (tap, weight) in weights.iter_mut().enumerate() {
+			// The distance from the output frame to this input frame.
+			let distance = fraction + half - 1.0 - tap as f64;
+
+			let value = match self.mode {
+				ResampleMode::Linear => (1.0 - distance.abs()).max(0.0),
+				ResampleMode::Sinc => {
+					let x = self.cutoff * distance;
+					let sinc = if x == 0.0 { 1.0 } else { (PI * x).sin() / (PI * x) };
+					let window = 0.42 + 0.5 * (PI * distance / half).cos() + 0.08 * (2.0 * PI * distance / half).cos();
+					sinc * window
+				}
+			};
+
+			*weight = value as f32;
+		}
+
+		// Normalize so a constant signal keeps the same level.
+		let sum: f32 = weights.iter().sum();
+		for weight in weights.iter_mut() {
+			*weight /= sum;
+		}
+	}
+
+	fn timestamp(&self, frames: u64) -> Timestamp {
+		let offset = Timestamp::from_micros(frames * 1_000_000 / self.output_rate as u64);
+		self.start.unwrap_or_default() + offset
+	}
+
+	fn audio_data(&self, output: AudioPlanes) -> Result<Option<AudioData>> {
+		if output.frames() == 0 {
+			return Ok(None);
+		}
+
+		let data = AudioData::new(
+			output.planes.iter().map(Vec::as_slice),
+			self.output_rate,
+			output.timestamp,
+		)?;
+		Ok(Some(data))
+	}
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+	while b != 0 {
+		(a, b) = (b, a % b);
+	}
+	a
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn sine(rate: u32, frequency: f64, frames: usize) -> Vec<f32> {
+		(0..frames)
+			.map(|i| (2.0 * PI * frequency * i as f64 / rate as f64).sin() as f32)
+			.collect()
+	}
+
+	// Returns the output samples and the timestamp and size of each chunk.
+	fn resample(resampler: &mut Resampler, input: &[f32], chunk: usize) -> (Vec<f32>, Vec<(Timestamp, usize)>) {
+		let mut output = Vec::new();
+		let mut chunks = Vec::new();
+
+		for (i, chunk) in input.chunks(chunk).enumerate() {
+			let timestamp =
+				Timestamp::from_micros((i * chunk.len()) as u64 * 1_000_000 / resampler.input_rate() as u64);
+			let planes = resampler.resample_planes(&[chunk], timestamp).unwrap();
+			chunks.push((planes.timestamp, planes.frames()));
+			output.extend_from_slice(&planes.planes[0]);
+		}
+
+		let planes = resampler.flush_planes();
+		chunks.push((planes.timestamp, planes.frames()));
+		output.extend_from_slice(&planes.planes[0]);
+
+		(output, chunks)
+	}
+
+	#[test]
+	fn linear() {
+		let mut resampler = Resampler::new(1, 1, 2, ResampleMode::Linear).unwrap();
+		let (output, _) = resample(&mut resampler, &[0.0, 1.0, 0.0, -1.0], 4);
+		assert_eq!(output, [0.0, 0.5, 1.0, 0.5, 0.0, -0.5, -1.0, -0.5]);
+	}
+
+	#[test]
+	fn sinc() {
+		let input = sine(48000, 1000.0, 4800);
+		let expected = sine(44100, 1000.0, 4410);
+
+		let mut resampler = Resampler::new(1, 48000, 44100, ResampleMode::Sinc).unwrap();
+		let (output, chunks) = resample(&mut resampler, &input, 480);
+		assert_eq!(output.len(), expected.len());
+
+		// The edges are distorted by the silence before and after the signal.
+		for (actual, expected) in output.iter().zip(&expected).skip(100).take(4200) {
+			assert!((actual - expected).abs() < 1e-3, "{actual} != {expected}");
+		}
+
+		// The first chunk is shortened by the kernel, then each chunk starts where the last one ended.
+		let mut frames = 0;
+		for (timestamp, size) in chunks {
+			assert_eq!(timestamp, Timestamp::from_micros(frames * 1_000_000 / 44100));
+			frames += size as u64;
+		}
+	}
+
+	#[test]
+	fn chunking() {
+		let input = sine(44100, 440.0, 2000);
+
+		for mode in [ResampleMode::Linear, ResampleMode::Sinc] {
+			for (rate, frames) in [(48000, 2177), (8000, 363)] {
+				let mut resampler = Resampler::new(1, 44100, rate, mode).unwrap();
+				let (whole, _) = resample(&mut resampler, &input, input.len());
+
+				// The resampler is reset by flush, so it can be reused.
+				let (chunked, _) = resample(&mut resampler, &input, 7);
+				assert_eq!(whole, chunked);
+				assert_eq!(whole.len(), frames);
+			}
+		}
+	}
+
+	#[test]
+	fn irregular() {
+		// Too many phases to precompute, so the kernel is computed on the fly.
+		let resampler = Resampler::new(1, 44100, 47999, ResampleMode::Sinc).unwrap();
+		assert!(resampler.table.is_none());
+
+		let mut resampler = resampler;
+		let (output, _) = resample(&mut resampler, &vec![0.5; 1000], 100);
+		assert_eq!(output.len(), 1089);
+		assert!(output[100..1000].iter().all(|s| (s - 0.5).abs() < 1e-3));
+	}
+
+	#[cfg(mock)]
+	#[test]
+	fn audio_data() {
+		let mut resampler = Resampler::new(2, 48000, 16000, ResampleMode::Sinc).unwrap();
+
+		let plane = vec![0.25; 960];
+		let mut timestamps = Vec::new();
+
+		for i in 0..5 {
+			let data = AudioData::new(
+				[&plane[..], &plane[..]].into_iter(),
+				48000,
+				Timestamp::from_millis(100 + i * 20),
+			)
+			.unwrap();
+
+			if let Some(output) = resampler.resample(&data).unwrap() {
+				assert_eq!(output.sample_rate(), 16000);
+				assert_eq!(output.number_of_channels(), 2);
+				timestamps.push((output.timestamp(), output.number_of_frames()));
+			}
+		}
+
+		let output = resampler.flush().unwrap().unwrap();
+		timestamps.push((output.timestamp(), output.number_of_frames()));
+
+		// Each output starts right after the previous one, at 16 frames per millisecond.
+		let mut frames = 0;
+		for (timestamp, size) in timestamps {
+			assert_eq!(
+				timestamp,
+				Timestamp::from_millis(100) + Timestamp::from_micros(frames * 1000 / 16)
+			);
+			frames += size as u64;
+		}
+		assert_eq!(frames, 1600);
+	}
+}
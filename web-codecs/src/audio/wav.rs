@@ -0,0 +1,410 @@
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use crate::{Error, Result, Timestamp};
+
+use super::{AudioData, AudioDataFormat};
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xfffe;
+
+// The size of the RIFF, fmt, and data chunk headers written by WavWriter.
+const HEADER_SIZE: u64 = 44;
+
+// The largest fmt chunk we'll read; WAVE_FORMAT_EXTENSIBLE is 40 bytes.
+const MAX_FMT_SIZE: u64 = 64;
+
+/// The stream parameters of a WAV file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WavHeader {
+	/// The interleaved sample format; only S16 and F32 are supported.
+	pub format: AudioDataFormat,
+
+	pub channel_count: u32,
+	pub sample_rate: u32,
+}
+
+impl WavHeader {
+	fn parse(fmt: &[u8]) -> Result<Self> {
+		if fmt.len() < 16 {
+			return Err(Error::InvalidHeader);
+		}
+
+		let u16_at = |i: usize| u16::from_le_bytes([fmt[i], fmt[i + 1]]);
+		let u32_at = |i: usize| u32::from_le_bytes([fmt[i], fmt[i + 1], fmt[i + 2], fmt[i + 3]]);
+
+		let tag = match u16_at(0) {
+			// The first two bytes of the sub-format GUID are the actual format tag.
+			WAVE_FORMAT_EXTENSIBLE if fmt.len() >= 26 => u16_at(24),
+			tag => tag,
+		};
+
+		let format = match (tag, u16_at(14)) {
+			(WAVE_FORMAT_PCM, 16) => AudioDataFormat::S16,
+			(WAVE_FORMAT_IEEE_FLOAT, 32) => AudioDataFormat::F32,
+			_ => return Err(Error::UnsupportedFormat),
+		};
+
+		let channel_count = u16_at(2) as u32;
+		if channel_count == 0 {
+			return Err(Error::NoChannels);
+		}
+
+		let sample_rate = u32_at(4);
+		if sample_rate == 0 {
+			return Err(Error::InvalidHeader);
+		}
+
+		Ok(Self {
+			format,
+			channel_count,
+			sample_rate,
+		})
+	}
+
+	fn encode(&self, data_size: u32) -> Result<[u8; HEADER_SIZE as usize]> {
+		let tag = match self.format {
+			AudioDataFormat::S16 => WAVE_FORMAT_PCM,
+			AudioDataFormat::F32 => WAVE_FORMAT_IEEE_FLOAT,
+			_ => return Err(Error::UnsupportedFormat),
+		};
+
+		// The header stores these as u16, so a huge channel count can't be represented.
+		let sample_size = self.sample_size()? as u16;
+		let channel_count = u16::try_from(self.channel_count).map_err(|_| Error::OutOfRange)?;
+		let block_align = sample_size.checked_mul(channel_count).ok_or(Error::OutOfRange)?;
+		let byte_rate = self
+			.sample_rate
+			.checked_mul(block_align as u32)
+			.ok_or(Error::OutOfRange)?;
+
+		let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+		header.extend_from_slice(b"RIFF");
+		header.extend_from_slice(&(data_size + HEADER_SIZE as u32 - 8).to_le_bytes());
+		header.extend_from_slice(b"WAVE");
+
+		header.extend_from_slice(b"fmt ");
+		header.extend_from_slice(&16u32.to_le_bytes());
+		header.extend_from_slice(&tag.to_le_bytes());
+		header.extend_from_slice(&channel_count.to_le_bytes());
+		header.extend_from_slice(&self.sample_rate.to_le_bytes());
+		header.extend_from_slice(&byte_rate.to_le_bytes());
+		header.extend_from_slice(&block_align.to_le_bytes());
+		header.extend_from_slice(&(sample_size * 8).to_le_bytes());
+
+		header.extend_from_slice(b"data");
+		header.extend_from_slice(&data_size.to_le_bytes());
+
+		Ok(header.try_into().unwrap())
+	}
+
+	fn sample_size(&self) -> Result<usize> {
+		match self.format {
+			AudioDataFormat::S16 => Ok(2),
+			AudioDataFormat::F32 => Ok(4),
+			_ => Err(Error::UnsupportedFormat),
+		}
+	}
+
+	fn timestamp(&self, frames: u64) -> Timestamp {
+		Timestamp::from_micros(frames * 1_000_000 / self.sample_rate as u64)
+	}
+}
+
+/// Reads interleaved PCM from a WAV file into [AudioData].
+pub struct WavReader<R: Read> {
+	reader: R,
+	header: WavHeader,
+
+	// The number of bytes left in the data chunk.
+	remaining: u64,
+
+	// The number of frames read so far, used for the timestamp.
+	position: u64,
+}
+
+impl<R: Read> WavReader<R> {
+	pub fn new(mut reader: R) -> Result<Self> {
+		let mut riff = [0; 12];
+		reader.read_exact(&mut riff)?;
+
+		if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+			return Err(Error::InvalidHeader);
+		}
+
+		let mut header = None;
+
+		loop {
+			let mut chunk = [0; 8];
+			reader.read_exact(&mut chunk)?;
+
+			let size = u32::from_le_bytes(chunk[4..8].try_into().unwrap()) as u64;
+
+			match &chunk[0..4] {
+				b"fmt " => {
+					// Avoid allocating an arbitrary amount for a corrupt file.
+					if size > MAX_FMT_SIZE {
+						return Err(Error::InvalidHeader);
+					}
+
+					let mut fmt = vec![0; size as usize];
+					reader.read_exact(&mut fmt)?;
+					header = Some(WavHeader::parse(&fmt)?);
+
+					// Chunks are padded to an even size.
+					io::copy(&mut reader.by_ref().take(size & 1), &mut io::sink())?;
+				}
+				b"data" => {
+					return Ok(Self {
+						reader,
+						header: header.ok_or(Error::InvalidHeader)?,
+						remaining: size,
+						position: 0,
+					});
+				}
+				_ => {
+					io::copy(&mut reader.by_ref().take(size + (size & 1)), &mut io::sink())?;
+				}
+			}
+		}
+	}
+
+	pub fn header(&self) -> &WavHeader {
+		&self.header
+	}
+
+	/// Read up to `max_frames` frames, returning None at the end of the data.
+	///
+	/// The timestamp of each [AudioData] continues from the previous one.
+	pub fn read(&mut self, max_frames: usize) -> Result<Option<AudioData>> {
+		let sample_size = self.header.sample_size()?;
+		let channel_count = self.header.channel_count as usize;
+		let block_align = (sample_size * channel_count) as u64;
+
+		let frames = (self.remaining / block_align).min(max_frames as u64) as usize;
+		if frames == 0 {
+			return Ok(None);
+		}
+
+		let mut bytes = vec![0; frames * block_align as usize];
+		self.reader.read_exact(&mut bytes)?;
+		self.remaining -= bytes.len() as u64;
+
+		let mut planes = vec![Vec::with_capacity(frames); channel_count];
+		for (i, sample) in bytes.chunks_exact(sample_size).enumerate() {
+			let sample = match self.header.format {
+				AudioDataFormat::S16 => i16::from_le_bytes(sample.try_into().unwrap()) as f32 / 32768.0,
+				_ => f32::from_le_bytes(sample.try_into().unwrap()),
+			};
+
+			planes[i % channel_count].push(sample);
+		}
+
+		let timestamp = self.header.timestamp(self.position);
+		self.position += frames as u64;
+
+		let data = AudioData::new(planes.iter().map(Vec::as_slice), self.header.sample_rate, timestamp)?;
+		Ok(Some(data))
+	}
+
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+}
+
+/// Writes [AudioData] to a WAV file as interleaved PCM.
+///
+/// The chunk sizes are unknown until the end, so [Self::finish] must be called to rewrite the header.
+pub struct WavWriter<W: Write + Seek> {
+	writer: W,
+	header: WavHeader,
+
+	// The position of the RIFF header, in case the writer didn't start at 0.
+	start: u64,
+
+	// The number of bytes written to the data chunk.
+	size: u64,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+	pub fn new(mut writer: W, header: WavHeader) -> Result<Self> {
+		if header.channel_count == 0 {
+			return Err(Error::NoChannels);
+		}
+
+		let start = writer.stream_position()?;
+		writer.write_all(&header.encode(0)?)?;
+
+		Ok(Self {
+			writer,
+			header,
+			start,
+			size: 0,
+		})
+	}
+
+	pub fn header(&self) -> &WavHeader {
+		&self.header
+	}
+
+	/// Write the samples, which must match the channel count and sample rate of the header.
+	pub fn write(&mut self, data: &AudioData) -> Result<()> {
+		let channel_count = self.header.channel_count as usize;
+
+		if data.number_of_channels() as usize != channel_count || data.sample_rate() != self.header.sample_rate {
+			return Err(Error::UnsupportedFormat);
+		}
+
+		let frames = data.number_of_frames() as usize;
+		let planes = data.to_planes()?;
+
+		let mut bytes = Vec::with_capacity(frames * channel_count * self.header.sample_size()?);
+		for frame in 0..frames {
+			for plane in &planes {
+				match self.header.format {
+					AudioDataFormat::S16 => {
+						let sample = (plane[frame] * 32768.0).round().clamp(i16::MIN as f32, i16::MAX as f32);
+						bytes.extend_from_slice(&(sample as i16).to_le_bytes());
+					}
+					_ => bytes.extend_from_slice(&plane[frame].to_le_bytes()),
+				}
+			}
+		}
+
+		self.writer.write_all(&bytes)?;
+		self.size += bytes.len() as u64;
+
+		Ok(())
+	}
+
+	/// Rewrite the header with the final sizes and return the writer.
+	pub fn finish(mut self) -> Result<W> {
+		let size = u32::try_from(self.size)
+			.ok()
+			.filter(|size| *size <= u32::MAX - HEADER_SIZE as u32)
+			.ok_or(Error::OutOfRange)?;
+
+		self.writer.seek(SeekFrom::Start(self.start))?;
+		self.writer.write_all(&self.header.encode(size)?)?;
+		self.writer
+			.seek(SeekFrom::Start(self.start + HEADER_SIZE + self.size))?;
+
+		Ok(self.writer)
+	}
+}
+
+#[cfg(all(test, mock))]
+mod test {
+	use std::io::Cursor;
+
+	use super::*;
+
+	fn write(format: AudioDataFormat) -> Vec<u8> {
+		let header = WavHeader {
+			format,
+			channel_count: 2,
+			sample_rate: 8000,
+		};
+
+		let mut writer = WavWriter::new(Cursor::new(Vec::new()), header).unwrap();
+
+		let left = [0.0, 0.5, -0.5, 1.0];
+		let right = [-1.0, 0.25, -0.25, 0.125];
+		let data = AudioData::new([&left[..], &right[..]].into_iter(), 8000, Timestamp::ZERO).unwrap();
+
+		writer.write(&data).unwrap();
+		writer.write(&data).unwrap();
+		writer.finish().unwrap().into_inner()
+	}
+
+	#[test]
+	fn roundtrip() {
+		for format in [AudioDataFormat::S16, AudioDataFormat::F32] {
+			let file = write(format);
+
+			let mut reader = WavReader::new(file.as_slice()).unwrap();
+			assert_eq!(reader.header().format, format);
+			assert_eq!(reader.header().channel_count, 2);
+			assert_eq!(reader.header().sample_rate, 8000);
+
+			let mut left = Vec::new();
+			let mut right = Vec::new();
+			let mut timestamps = Vec::new();
+
+			while let Some(data) = reader.read(3).unwrap() {
+				timestamps.push(data.timestamp().as_micros());
+				data.append_to(&mut left, 0, Default::default()).unwrap();
+				data.append_to(&mut right, 1, Default::default()).unwrap();
+			}
+
+			// 8 frames in chunks of 3, at 125us per frame.
+			assert_eq!(timestamps, [0, 375, 750]);
+
+			// Full scale is clipped when converting to S16, otherwise the samples are exact.
+			let max = match format {
+				AudioDataFormat::S16 => 32767.0 / 32768.0,
+				_ => 1.0,
+			};
+
+			assert_eq!(left, [0.0, 0.5, -0.5, max, 0.0, 0.5, -0.5, max]);
+			assert_eq!(right, [-1.0, 0.25, -0.25, 0.125, -1.0, 0.25, -0.25, 0.125]);
+		}
+	}
+
+	#[test]
+	fn header() {
+		let file = write(AudioDataFormat::S16);
+		assert_eq!(&file[0..4], b"RIFF");
+		assert_eq!(u32::from_le_bytes(file[4..8].try_into().unwrap()), 36 + 32);
+		assert_eq!(&file[36..40], b"data");
+		assert_eq!(u32::from_le_bytes(file[40..44].try_into().unwrap()), 32);
+		assert_eq!(file.len(), 44 + 32);
+
+		// Insert an unknown (odd sized) chunk before the data.
+		let mut patched = file[..36].to_vec();
+		patched.extend_from_slice(b"LIST\x03\x00\x00\x00abc\x00");
+		patched.extend_from_slice(&file[36..]);
+
+		let mut reader = WavReader::new(patched.as_slice()).unwrap();
+		assert_eq!(reader.read(100).unwrap().unwrap().number_of_frames(), 8);
+	}
+
+	#[test]
+	fn invalid() {
+		let file = write(AudioDataFormat::S16);
+
+		// A zero sample rate would divide by zero when computing timestamps.
+		let mut patched = file.clone();
+		patched[24..28].copy_from_slice(&0u32.to_le_bytes());
+		assert!(matches!(WavReader::new(patched.as_slice()), Err(Error::InvalidHeader)));
+
+		// A huge fmt chunk is rejected before allocating.
+		let mut patched = file.clone();
+		patched[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+		assert!(matches!(WavReader::new(patched.as_slice()), Err(Error::InvalidHeader)));
+
+		// The byte rate doesn't fit in the header.
+		let header = WavHeader {
+			format: AudioDataFormat::F32,
+			channel_count: 2,
+			sample_rate: u32::MAX,
+		};
+		assert!(matches!(
+			WavWriter::new(Cursor::new(Vec::new()), header),
+			Err(Error::OutOfRange)
+		));
+
+		// Neither the channel count nor the block align fit in a u16.
+		for channel_count in [65536, 32768] {
+			let header = WavHeader {
+				format: AudioDataFormat::F32,
+				channel_count,
+				sample_rate: 1,
+			};
+			assert!(matches!(
+				WavWriter::new(Cursor::new(Vec::new()), header),
+				Err(Error::OutOfRange)
+			));
+		}
+	}
+}
@@ -1,6 +1,9 @@
+use std::sync::Arc;
+
 use wasm_bindgen::prelude::*;
 
 #[derive(Debug, thiserror::Error, Clone)]
+#[non_exhaustive]
 pub enum Error {
 	#[error("dropped")]
 	Dropped,
@@ -11,6 +14,28 @@ pub enum Error {
 	#[error("no channels")]
 	NoChannels,
 
+	#[error("unsupported format")]
+	UnsupportedFormat,
+
+	#[error("invalid payload")]
+	InvalidPayload,
+
+	#[error("keyframe required")]
+	KeyframeRequired,
+
+	#[error("out of range")]
+	OutOfRange,
+
+	#[error("invalid header")]
+	InvalidHeader,
+
+	#[error("io error: {0}")]
+	Io(Arc<std::io::Error>),
+
+	#[cfg(feature = "track")]
+	#[error("stream error: {0}")]
+	Stream(#[from] web_streams::Error),
+
 	#[error("unknown error: {0:?}")]
 	Unknown(JsValue),
 }
@@ -21,4 +46,10 @@ impl From<JsValue> for Error {
 	}
 }
 
+impl From<std::io::Error> for Error {
+	fn from(e: std::io::Error) -> Self {
+		Self::Io(Arc::new(e))
+	}
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
@@ -1,13 +1,35 @@
 use std::fmt;
 
-use bytes::{Bytes, BytesMut};
-
-use crate::Timestamp;
+use crate::{EncodedPayload, Timestamp};
 
 pub struct EncodedFrame {
-	pub payload: Bytes,
+	pub payload: EncodedPayload,
 	pub timestamp: Timestamp,
 	pub keyframe: bool,
+
+	/// The SVC temporal layer, if the encoder was configured with a [crate::ScalabilityMode].
+	pub temporal_layer: Option<u8>,
+
+	/// The encoded alpha channel, if the encoder was configured with `alpha_preserved`.
+	/// It's a separate bitstream using the same codec; see [Self::alpha_frame].
+	pub alpha: Option<EncodedPayload>,
+
+	/// Set when the encoder emitted a new decoder config with this frame, ex. after reconfiguring.
+	pub config_changed: bool,
+}
+
+impl EncodedFrame {
+	/// Returns the alpha side data as a frame, to be decoded by a second decoder with the same config.
+	pub fn alpha_frame(&self) -> Option<EncodedFrame> {
+		Some(EncodedFrame {
+			payload: self.alpha.clone()?,
+			timestamp: self.timestamp,
+			keyframe: self.keyframe,
+			temporal_layer: self.temporal_layer,
+			alpha: None,
+			config_changed: self.config_changed,
+		})
+	}
 }
 
 impl fmt::Debug for EncodedFrame {
@@ -16,38 +38,67 @@ impl fmt::Debug for EncodedFrame {
 			.field("payload", &self.payload.len())
 			.field("timestamp", &self.timestamp)
 			.field("keyframe", &self.keyframe)
+			.field("temporal_layer", &self.temporal_layer)
+			.field("alpha", &self.alpha.as_ref().map(|alpha| alpha.len()))
+			.field("config_changed", &self.config_changed)
 			.finish()
 	}
 }
 
+// The payload is not copied until it's accessed.
 impl From<web_sys::EncodedVideoChunk> for EncodedFrame {
 	fn from(chunk: web_sys::EncodedVideoChunk) -> Self {
-		let size = chunk.byte_length() as usize;
-
-		let mut payload = BytesMut::with_capacity(size);
-		payload.resize(size, 0);
-		chunk.copy_to_with_u8_slice(&mut payload).unwrap();
-
 		Self {
-			payload: payload.freeze(),
 			timestamp: Timestamp::from_micros(chunk.timestamp() as _),
 			keyframe: chunk.type_() == web_sys::EncodedVideoChunkType::Key,
+			payload: chunk.into(),
+			temporal_layer: None,
+			alpha: None,
+			config_changed: false,
 		}
 	}
 }
 
+// The payload is not copied until it's accessed.
 impl From<web_sys::EncodedAudioChunk> for EncodedFrame {
 	fn from(chunk: web_sys::EncodedAudioChunk) -> Self {
-		let size = chunk.byte_length() as usize;
-
-		let mut payload = BytesMut::with_capacity(size);
-		payload.resize(size, 0);
-		chunk.copy_to_with_u8_slice(&mut payload).unwrap();
-
 		Self {
-			payload: payload.freeze(),
 			timestamp: Timestamp::from_micros(chunk.timestamp() as _),
 			keyframe: chunk.type_() == web_sys::EncodedAudioChunkType::Key,
+			payload: chunk.into(),
+			temporal_layer: None,
+			alpha: None,
+			config_changed: false,
 		}
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use std::time::Duration;
+
+	use bytes::Bytes;
+
+	use super::*;
+
+	#[test]
+	fn alpha_frame() {
+		let frame = EncodedFrame {
+			payload: Bytes::from_static(b"color").into(),
+			timestamp: Duration::from_millis(33),
+			keyframe: true,
+			temporal_layer: Some(0),
+			alpha: Some(Bytes::from_static(b"alpha").into()),
+			config_changed: false,
+		};
+
+		let alpha = frame.alpha_frame().unwrap();
+		assert_eq!(&*alpha.payload, b"alpha");
+		assert_eq!(alpha.timestamp, frame.timestamp);
+		assert!(alpha.keyframe);
+		assert!(alpha.alpha.is_none());
+
+		let opaque = EncodedFrame { alpha: None, ..alpha };
+		assert!(opaque.alpha_frame().is_none());
+	}
+}
@@ -0,0 +1,261 @@
+use std::{cell::Cell, rc::Rc};
+
+use bytes::Bytes;
+use derive_more::From;
+
+use crate::{Dimensions, Error, Result, VideoFrame};
+
+#[cfg(mock)]
+use crate::mock::ImageDecoder as ImageDecoderCodec;
+
+/// The encoded image, either fully buffered or streamed as it downloads.
+#[derive(Debug, Clone, From)]
+pub enum ImageSource {
+	Bytes(Bytes),
+
+	/// A stream of [js_sys::Uint8Array] chunks, ex. a fetch response body.
+	Stream(web_sys::ReadableStream),
+}
+
+impl From<Vec<u8>> for ImageSource {
+	fn from(data: Vec<u8>) -> Self {
+		Self::Bytes(data.into())
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct ImageDecoderConfig {
+	pub source: ImageSource,
+
+	/// The MIME type of the image, ex. `image/gif`.
+	pub mime_type: String,
+
+	/// Select the animated track by default, if there is one.
+	pub prefer_animation: Option<bool>,
+
+	/// Scale the decoded frames to this resolution.
+	pub desired: Option<Dimensions>,
+}
+
+impl ImageDecoderConfig {
+	pub fn new<S: Into<ImageSource>, T: Into<String>>(source: S, mime_type: T) -> Self {
+		Self {
+			source: source.into(),
+			mime_type: mime_type.into(),
+			prefer_animation: None,
+			desired: None,
+		}
+	}
+
+	pub fn build(self) -> Result<(ImageDecoder, ImageDecoded)> {
+		if let Some(desired) = self.desired {
+			if desired.width == 0 || desired.height == 0 {
+				return Err(Error::InvalidDimensions);
+			}
+		}
+
+		let codec = Rc::new(ImageDecoderCodec::new(&self)?);
+		let index = Rc::new(Cell::new(0));
+
+		let decoder = ImageDecoder {
+			codec: codec.clone(),
+			index: index.clone(),
+		};
+		let decoded = ImageDecoded { codec, index };
+
+		Ok((decoder, decoded))
+	}
+}
+
+/// A track within an image, ex. the still and animated versions of an AVIF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageTrack {
+	pub animated: bool,
+
+	/// The number of frames decoded so far, which can grow while the image is still streaming.
+	pub frame_count: u32,
+
+	/// The number of times the animation repeats after the first play, or None if it loops forever.
+	pub repetition_count: Option<u32>,
+
+	pub selected: bool,
+}
+
+/// A decoded frame; use [VideoFrame::duration] for how long to display it.
+#[derive(Debug)]
+pub struct ImageFrame {
+	pub frame: VideoFrame,
+
+	/// False if the frame is a progressive preview, ex. an interlaced PNG that hasn't fully loaded.
+	pub complete: bool,
+}
+
+/// Controls the track selection and decodes specific frames.
+pub struct ImageDecoder {
+	codec: Rc<ImageDecoderCodec>,
+
+	// The next frame returned by ImageDecoded, reset when the track changes.
+	index: Rc<Cell<u32>>,
+}
+
+impl ImageDecoder {
+	/// Check if the browser can decode the given MIME type.
+	pub async fn is_type_supported(mime_type: &str) -> Result<bool> {
+		ImageDecoderCodec::is_type_supported(mime_type).await
+	}
+
+	/// Returns the tracks, waiting until enough of the image has been parsed to know them.
+	pub async fn tracks(&self) -> Result<Vec<ImageTrack>> {
+		self.codec.tracks().await
+	}
+
+	/// Select a different track, restarting [ImageDecoded] from its first frame.
+	pub async fn select_track(&self, index: usize) -> Result<()> {
+		self.codec.select_track(index).await?;
+		self.index.set(0);
+
+		Ok(())
+	}
+
+	/// Decode the frame at the given index of the selected track.
+	pub async fn decode(&self, index: u32) -> Result<ImageFrame> {
+		self.codec.decode(index).await
+	}
+
+	/// Returns true once all of the encoded data has been received.
+	pub fn complete(&self) -> bool {
+		self.codec.complete()
+	}
+
+	/// Wait until all of the encoded data has been received.
+	pub async fn completed(&self) -> Result<()> {
+		self.codec.completed().await
+	}
+}
+
+/// Returns each frame of the selected track in order.
+pub struct ImageDecoded {
+	codec: Rc<ImageDecoderCodec>,
+	index: Rc<Cell<u32>>,
+}
+
+impl ImageDecoded {
+	/// Decode the next frame, or None after the last frame.
+	///
+	/// Animations are not repeated; start over with [ImageDecoder::decode] based on [ImageTrack::repetition_count].
+	pub async fn next(&mut self) -> Result<Option<ImageFrame>> {
+		loop {
+			let index = self.index.get();
+
+			let tracks = self.codec.tracks().await?;
+			let Some(track) = tracks.iter().find(|track| track.selected) else {
+				return Ok(None);
+			};
+
+			if index < track.frame_count {
+				let frame = self.codec.decode(index).await?;
+				self.index.set(index + 1);
+				return Ok(Some(frame));
+			}
+
+			if self.codec.complete() {
+				return Ok(None);
+			}
+
+			// Wait for the rest of the image before checking the frame count again.
+			self.codec.completed().await?;
+		}
+	}
+}
+
+#[cfg(not(mock))]
+struct ImageDecoderCodec(web_sys::ImageDecoder);
+
+#[cfg(not(mock))]
+impl ImageDecoderCodec {
+	fn new(config: &ImageDecoderConfig) -> Result<Self> {
+		let data: wasm_bindgen::JsValue = match &config.source {
+			ImageSource::Bytes(data) => js_sys::Uint8Array::from(data.as_ref()).into(),
+			ImageSource::Stream(stream) => stream.into(),
+		};
+
+		let init = web_sys::ImageDecoderInit::new(&data, &config.mime_type);
+
+		if let Some(prefer) = config.prefer_animation {
+			init.set_prefer_animation(prefer);
+		}
+
+		if let Some(Dimensions { width, height }) = config.desired {
+			init.set_desired_width(width);
+			init.set_desired_height(height);
+		}
+
+		Ok(Self(web_sys::ImageDecoder::new(&init)?))
+	}
+
+	async fn is_type_supported(mime_type: &str) -> Result<bool> {
+		let supported =
+			wasm_bindgen_futures::JsFuture::from(web_sys::ImageDecoder::is_type_supported(mime_type)).await?;
+		Ok(supported.as_bool().unwrap_or(false))
+	}
+
+	async fn tracks(&self) -> Result<Vec<ImageTrack>> {
+		let tracks = self.0.tracks();
+		wasm_bindgen_futures::JsFuture::from(tracks.ready()).await?;
+
+		let tracks = (0..tracks.length())
+			.filter_map(|index| tracks.get(index))
+			.map(|track| {
+				let repetition_count = track.repetition_count();
+
+				ImageTrack {
+					animated: track.animated(),
+					frame_count: track.frame_count(),
+					repetition_count: repetition_count.is_finite().then_some(repetition_count as u32),
+					selected: track.selected(),
+				}
+			})
+			.collect();
+
+		Ok(tracks)
+	}
+
+	async fn select_track(&self, index: usize) -> Result<()> {
+		let tracks = self.0.tracks();
+		wasm_bindgen_futures::JsFuture::from(tracks.ready()).await?;
+
+		let track = tracks.get(index as u32).ok_or(Error::OutOfRange)?;
+		track.set_selected(true);
+
+		Ok(())
+	}
+
+	async fn decode(&self, index: u32) -> Result<ImageFrame> {
+		let options = web_sys::ImageDecodeOptions::new();
+		options.set_frame_index(index);
+
+		let result = wasm_bindgen_futures::JsFuture::from(self.0.decode_with_options(&options)).await?;
+		let result: web_sys::ImageDecodeResult = wasm_bindgen::JsCast::unchecked_into(result);
+
+		Ok(ImageFrame {
+			frame: result.get_image().into(),
+			complete: result.get_complete(),
+		})
+	}
+
+	fn complete(&self) -> bool {
+		self.0.complete()
+	}
+
+	async fn completed(&self) -> Result<()> {
+		wasm_bindgen_futures::JsFuture::from(self.0.completed()).await?;
+		Ok(())
+	}
+}
+
+#[cfg(not(mock))]
+impl Drop for ImageDecoderCodec {
+	fn drop(&mut self) {
+		self.0.close();
+	}
+}
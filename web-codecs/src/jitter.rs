@@ -0,0 +1,432 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use crate::{EncodedFrame, Timestamp};
+
+#[derive(Debug, Clone)]
+pub struct JitterConfig {
+	/// The target delay never drops below this, even on a perfect network.
+	pub min_delay: Duration,
+
+	/// The target delay never exceeds this, no matter how bad the jitter.
+	pub max_delay: Duration,
+
+	/// The target delay is the measured jitter multiplied by this factor.
+	pub jitter_multiplier: f64,
+}
+
+impl Default for JitterConfig {
+	fn default() -> Self {
+		Self {
+			min_delay: Duration::ZERO,
+			max_delay: Duration::from_millis(500),
+			jitter_multiplier: 3.0,
+		}
+	}
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct JitterStats {
+	/// The number of frames pushed, including the ones that were discarded.
+	pub received: u64,
+
+	/// The number of frames returned by [JitterBuffer::pop].
+	pub released: u64,
+
+	/// Frames that arrived after a later frame was released, or duplicates.
+	pub late: u64,
+
+	/// Gaps in the sequence number, only detected when using [JitterBuffer::push_sequenced].
+	pub lost: u64,
+
+	/// Frames that arrived with an older timestamp than a previous frame.
+	pub reordered: u64,
+
+	/// Delta frames discarded while waiting for a keyframe.
+	pub skipped: u64,
+
+	/// The smoothed interarrival jitter.
+	pub jitter: Duration,
+
+	/// The current target delay.
+	pub delay: Duration,
+}
+
+#[derive(Debug)]
+struct Entry {
+	frame: EncodedFrame,
+	sequence: Option<u64>,
+}
+
+/// Reorders [EncodedFrame]s received over an unreliable network before they're decoded.
+///
+/// Each frame is held until its timestamp plus the target delay, relative to the fastest frame seen so far,
+/// giving late frames a chance to fill the gap. The target delay adapts to the measured interarrival jitter.
+/// If a gap can't be filled, delta frames are skipped until the next keyframe so the decoder never sees a
+/// broken reference. Like [crate::PlayoutClock], each method takes `now` from a monotonic wall clock.
+///
+/// Frames don't carry a duration, so a lost frame can only be detected with a transport sequence number: use
+/// [Self::push_sequenced] for loss recovery. With [Self::push], only frames that arrive too late are detected.
+#[derive(Debug)]
+pub struct JitterBuffer {
+	config: JitterConfig,
+	frames: BTreeMap<Timestamp, Entry>,
+
+	// The smallest (arrival - timestamp) seen so far in microseconds, used to map timestamps to wall clock time.
+	transit: Option<i64>,
+
+	// The arrival time and timestamp of the previous frame, and the smoothed jitter in microseconds.
+	previous: Option<(Duration, Timestamp)>,
+	jitter: f64,
+
+	// The newest timestamp pushed, used to detect reordering.
+	newest: Option<Timestamp>,
+
+	// The last frame released, used to detect late frames and gaps.
+	released: Option<Timestamp>,
+	sequence: Option<u64>,
+
+	// Set initially and after a gap, discarding delta frames.
+	keyframe_required: bool,
+
+	stats: JitterStats,
+}
+
+impl JitterBuffer {
+	pub fn new(config: JitterConfig) -> Self {
+		let delay = config.min_delay;
+
+		Self {
+			config,
+			frames: BTreeMap::new(),
+			transit: None,
+			previous: None,
+			jitter: 0.0,
+			newest: None,
+			released: None,
+			sequence: None,
+			keyframe_required: true,
+			stats: JitterStats {
+				delay,
+				..Default::default()
+			},
+		}
+	}
+
+	/// Insert a frame received at the given wall clock time.
+	///
+	/// A frame that never arrives is not detected; see [Self::push_sequenced].
+	pub fn push(&mut self, now: Duration, frame: EncodedFrame) {
+		self.insert(now, frame, None)
+	}
+
+	/// Insert a frame with a transport sequence number, which is used to detect lost frames.
+	pub fn push_sequenced(&mut self, now: Duration, sequence: u64, frame: EncodedFrame) {
+		self.insert(now, frame, Some(sequence))
+	}
+
+	fn insert(&mut self, now: Duration, frame: EncodedFrame, sequence: Option<u64>) {
+		self.stats.received += 1;
+
+		let timestamp = frame.timestamp;
+
+		// It's too late to decode this frame, and anything after it may depend on it.
+		if self.released.is_some_and(|released| timestamp <= released) || self.frames.contains_key(&timestamp) {
+			self.stats.late += 1;
+
+			if !frame.keyframe && self.released.is_some_and(|released| timestamp < released) {
+				self.keyframe_required = true;
+			}
+
+			return;
+		}
+
+		if self.newest.is_some_and(|newest| timestamp < newest) {
+			self.stats.reordered += 1;
+		}
+		self.newest = self.newest.max(Some(timestamp));
+
+		self.measure(now, timestamp);
+		self.frames.insert(timestamp, Entry { frame, sequence });
+	}
+
+	// Update the transit time and the RFC 3550 interarrival jitter.
+	fn measure(&mut self, now: Duration, timestamp: Timestamp) {
+		let transit = micros(now) - micros(timestamp);
+		self.transit = Some(self.transit.map_or(transit, |min| min.min(transit)));
+
+		if let Some((arrival, previous)) = self.previous {
+			let difference = (micros(now) - micros(arrival)) - (micros(timestamp) - micros(previous));
+			self.jitter += (difference.abs() as f64 - self.jitter) / 16.0;
+		}
+		self.previous = Some((now, timestamp));
+
+		let delay = Duration::from_micros((self.jitter * self.config.jitter_multiplier) as u64);
+		self.stats.jitter = Duration::from_micros(self.jitter as u64);
+		self.stats.delay = delay.clamp(self.config.min_delay, self.config.max_delay);
+	}
+
+	/// Return the next frame if it's due, skipping delta frames while a keyframe is required.
+	pub fn pop(&mut self, now: Duration) -> Option<EncodedFrame> {
+		loop {
+			if self.keyframe_required {
+				self.skip_to_keyframe();
+			}
+
+			let deadline = self.deadline()?;
+			if now < deadline {
+				return None;
+			}
+
+			let (timestamp, entry) = self.frames.pop_first()?;
+			self.released = Some(timestamp);
+
+			self.advance(entry.sequence);
+
+			if self.keyframe_required && !entry.frame.keyframe {
+				self.stats.skipped += 1;
+				continue;
+			}
+
+			self.keyframe_required = false;
+			self.stats.released += 1;
+
+			return Some(entry.frame);
+		}
+	}
+
+	// Don't wait for frames we're going to skip anyway if there's a keyframe buffered.
+	fn skip_to_keyframe(&mut self) {
+		let Some(keyframe) = self
+			.frames
+			.iter()
+			.find(|(_, entry)| entry.frame.keyframe)
+			.map(|(ts, _)| *ts)
+		else {
+			return;
+		};
+
+		while let Some(entry) = self.frames.first_entry() {
+			if *entry.key() == keyframe {
+				break;
+			}
+
+			let (timestamp, entry) = entry.remove_entry();
+			self.released = Some(timestamp);
+			self.advance(entry.sequence);
+			self.stats.skipped += 1;
+		}
+	}
+
+	// Count any gap in the sequence number, which requires a keyframe to recover.
+	fn advance(&mut self, sequence: Option<u64>) {
+		let Some(sequence) = sequence else {
+			return;
+		};
+
+		let expected = self.sequence.map_or(sequence, |last| last + 1);
+		if sequence > expected {
+			self.stats.lost += sequence - expected;
+			self.keyframe_required = true;
+		}
+
+		self.sequence = Some(sequence);
+	}
+
+	/// The wall clock time when the next frame is due, or None if the buffer is empty.
+	pub fn deadline(&self) -> Option<Duration> {
+		let (timestamp, _) = self.frames.first_key_value()?;
+		let deadline = micros(*timestamp) + self.transit? + micros(self.stats.delay);
+		Some(Duration::from_micros(deadline.max(0) as u64))
+	}
+
+	pub fn delay(&self) -> Duration {
+		self.stats.delay
+	}
+
+	pub fn stats(&self) -> JitterStats {
+		self.stats
+	}
+
+	pub fn len(&self) -> usize {
+		self.frames.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.frames.is_empty()
+	}
+
+	/// Discard all frames and measurements, for example after a seek.
+	/// The statistics are kept.
+	pub fn reset(&mut self) {
+		let stats = self.stats;
+		*self = Self::new(self.config.clone());
+
+		self.stats = JitterStats {
+			jitter: Duration::ZERO,
+			delay: self.stats.delay,
+			..stats
+		};
+	}
+}
+
+impl Default for JitterBuffer {
+	fn default() -> Self {
+		Self::new(JitterConfig::default())
+	}
+}
+
+fn micros(duration: Duration) -> i64 {
+	duration.as_micros() as i64
+}
+
+#[cfg(test)]
+mod test {
+	use bytes::Bytes;
+
+	use super::*;
+
+	fn ms(ms: u64) -> Duration {
+		Duration::from_millis(ms)
+	}
+
+	fn frame(timestamp: u64, keyframe: bool) -> EncodedFrame {
+		EncodedFrame {
+			payload: Bytes::new().into(),
+			timestamp: ms(timestamp),
+			keyframe,
+			temporal_layer: None,
+			alpha: None,
+			config_changed: false,
+		}
+	}
+
+	fn fixed() -> JitterBuffer {
+		JitterBuffer::new(JitterConfig {
+			min_delay: ms(50),
+			max_delay: ms(50),
+			..Default::default()
+		})
+	}
+
+	#[test]
+	fn reorder() {
+		let mut buffer = fixed();
+
+		buffer.push(ms(1000), frame(0, true));
+		buffer.push(ms(1040), frame(40, false));
+		buffer.push(ms(1045), frame(20, false));
+
+		assert_eq!(buffer.deadline(), Some(ms(1050)));
+		assert!(buffer.pop(ms(1049)).is_none());
+
+		let timestamps: Vec<_> = std::iter::from_fn(|| buffer.pop(ms(1100)))
+			.map(|frame| frame.timestamp)
+			.collect();
+		assert_eq!(timestamps, [ms(0), ms(20), ms(40)]);
+
+		let stats = buffer.stats();
+		assert_eq!(stats.reordered, 1);
+		assert_eq!(stats.released, 3);
+		assert_eq!(stats.late, 0);
+	}
+
+	#[test]
+	fn late() {
+		let mut buffer = fixed();
+
+		buffer.push(ms(1000), frame(0, true));
+		buffer.push(ms(1040), frame(40, false));
+		assert_eq!(buffer.pop(ms(1050)).unwrap().timestamp, ms(0));
+		assert_eq!(buffer.pop(ms(1090)).unwrap().timestamp, ms(40));
+
+		// The frame in between missed its chance, so skip to the next keyframe.
+		buffer.push(ms(1100), frame(20, false));
+		buffer.push(ms(1100), frame(60, false));
+		buffer.push(ms(1100), frame(80, true));
+		buffer.push(ms(1100), frame(100, false));
+
+		// The keyframe is released without waiting for the skipped frame's deadline.
+		assert_eq!(buffer.pop(ms(1130)).unwrap().timestamp, ms(80));
+		assert_eq!(buffer.pop(ms(1150)).unwrap().timestamp, ms(100));
+
+		let stats = buffer.stats();
+		assert_eq!(stats.late, 1);
+		assert_eq!(stats.skipped, 1);
+		assert_eq!(stats.released, 4);
+	}
+
+	#[test]
+	fn lost() {
+		let mut buffer = fixed();
+
+		buffer.push_sequenced(ms(1000), 0, frame(0, true));
+		buffer.push_sequenced(ms(1020), 1, frame(20, false));
+		buffer.push_sequenced(ms(1060), 3, frame(60, false));
+		buffer.push_sequenced(ms(1080), 4, frame(80, false));
+		buffer.push_sequenced(ms(1100), 5, frame(100, true));
+
+		let timestamps: Vec<_> = std::iter::from_fn(|| buffer.pop(ms(1200)))
+			.map(|frame| frame.timestamp)
+			.collect();
+		assert_eq!(timestamps, [ms(0), ms(20), ms(100)]);
+
+		let stats = buffer.stats();
+		assert_eq!(stats.lost, 1);
+		assert_eq!(stats.skipped, 2);
+	}
+
+	#[test]
+	fn lost_unsequenced() {
+		let mut buffer = fixed();
+
+		// The frame at 40ms never arrives, which can't be detected without a sequence number.
+		buffer.push(ms(1000), frame(0, true));
+		buffer.push(ms(1020), frame(20, false));
+		buffer.push(ms(1060), frame(60, false));
+		buffer.push(ms(1080), frame(80, false));
+
+		let timestamps: Vec<_> = std::iter::from_fn(|| buffer.pop(ms(1200)))
+			.map(|frame| frame.timestamp)
+			.collect();
+		assert_eq!(timestamps, [ms(0), ms(20), ms(60), ms(80)]);
+
+		let stats = buffer.stats();
+		assert_eq!(stats.lost, 0);
+		assert_eq!(stats.skipped, 0);
+	}
+
+	#[test]
+	fn keyframe_required() {
+		let mut buffer = fixed();
+
+		// Nothing can be decoded until the first keyframe.
+		buffer.push(ms(1000), frame(0, false));
+		assert!(buffer.pop(ms(2000)).is_none());
+		assert_eq!(buffer.stats().skipped, 1);
+
+		buffer.push(ms(2000), frame(1000, true));
+		assert!(buffer.pop(ms(2100)).unwrap().keyframe);
+	}
+
+	#[test]
+	fn adaptive() {
+		let mut buffer = JitterBuffer::default();
+		assert_eq!(buffer.delay(), Duration::ZERO);
+
+		// Frames every 20ms that arrive +/- 10ms.
+		for i in 0..100 {
+			let offset = if i % 2 == 0 { 0 } else { 20 };
+			buffer.push(ms(1000 + i * 20 + offset), frame(i * 20, i == 0));
+		}
+
+		let delay = buffer.delay();
+		assert!(delay > ms(40) && delay < ms(70), "{delay:?}");
+
+		// The delay is capped.
+		for i in 100..200 {
+			let offset = if i % 2 == 0 { 0 } else { 1000 };
+			buffer.push(ms(1000 + i * 20 + offset), frame(i * 20, false));
+		}
+		assert_eq!(buffer.delay(), ms(500));
+	}
+}
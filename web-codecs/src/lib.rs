@@ -2,11 +2,30 @@
 mod audio;
 mod error;
 mod frame;
+mod image;
+mod jitter;
+mod payload;
+mod playout;
+#[cfg(all(feature = "track", not(mock)))]
+mod track;
 mod video;
 
+#[cfg(mock)]
+pub mod mock;
+
 pub use audio::*;
 pub use error::*;
 pub use frame::*;
+pub use image::*;
+pub use jitter::*;
+pub use payload::*;
+pub use playout::*;
+#[cfg(all(feature = "track", not(mock)))]
+pub use track::*;
 pub use video::*;
 
 pub type Timestamp = std::time::Duration;
+
+// WebCodecs isn't available in Node.
+#[cfg(all(test, target_arch = "wasm32"))]
+wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
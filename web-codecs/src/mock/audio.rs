@@ -0,0 +1,246 @@
+use std::cell::Cell;
+
+use bytes::{Buf, BufMut, BytesMut};
+use tokio::sync::{mpsc, watch};
+
+use crate::{
+	AudioDataFormat, AudioDecoderConfig, AudioEncoderConfig, AudioEncoderOutput, EncodedFrame, Error, Result, Timestamp,
+};
+
+// channels (u32) + frames (u32) + sample rate (u32)
+const HEADER_SIZE: usize = 12;
+
+/// Raw planar f32 audio stored in memory, used in place of [web_sys::AudioData].
+///
+/// The getters mirror [web_sys::AudioData] so [crate::AudioData] can dereference to either.
+#[derive(Debug, Clone)]
+pub struct AudioData {
+	sample_rate: u32,
+	channels: usize,
+	frames: usize,
+	timestamp: Timestamp,
+
+	// Each channel is stored contiguously, one after the other.
+	data: Vec<f32>,
+}
+
+impl AudioData {
+	pub(crate) fn new<'a>(
+		channels: impl ExactSizeIterator<Item = &'a [f32]>,
+		sample_rate: u32,
+		timestamp: Timestamp,
+	) -> Result<Self> {
+		let mut channels = channels.peekable();
+		let channel_count = channels.len();
+		let frame_count = channels.peek().ok_or(Error::NoChannels)?.len();
+
+		let mut data = Vec::with_capacity(channel_count * frame_count);
+		for channel in channels {
+			if channel.len() != frame_count {
+				return Err(Error::OutOfRange);
+			}
+
+			data.extend_from_slice(channel);
+		}
+
+		Ok(Self {
+			sample_rate,
+			channels: channel_count,
+			frames: frame_count,
+			timestamp,
+			data,
+		})
+	}
+
+	pub fn format(&self) -> Option<AudioDataFormat> {
+		Some(AudioDataFormat::F32Planar)
+	}
+
+	pub fn sample_rate(&self) -> f32 {
+		self.sample_rate as f32
+	}
+
+	pub fn number_of_frames(&self) -> u32 {
+		self.frames as u32
+	}
+
+	pub fn number_of_channels(&self) -> u32 {
+		self.channels as u32
+	}
+
+	/// The duration in microseconds.
+	pub fn duration(&self) -> f64 {
+		self.frames as f64 * 1_000_000.0 / self.sample_rate as f64
+	}
+
+	/// The timestamp in microseconds.
+	pub fn timestamp(&self) -> f64 {
+		self.timestamp.as_micros() as _
+	}
+
+	/// The samples for the given channel.
+	pub fn plane(&self, channel: usize) -> Option<&[f32]> {
+		if channel >= self.channels {
+			return None;
+		}
+
+		Some(&self.data[channel * self.frames..(channel + 1) * self.frames])
+	}
+
+	pub(crate) fn copy_to(
+		&self,
+		dst: &mut [u8],
+		channel: usize,
+		offset: usize,
+		count: Option<usize>,
+		format: Option<AudioDataFormat>,
+	) -> Result<()> {
+		if format.is_some_and(|format| format != AudioDataFormat::F32Planar) {
+			return Err(Error::UnsupportedFormat);
+		}
+
+		let plane = self.plane(channel).ok_or(Error::OutOfRange)?;
+		let plane = plane.get(offset..).ok_or(Error::OutOfRange)?;
+		let plane = plane.get(..count.unwrap_or(plane.len())).ok_or(Error::OutOfRange)?;
+
+		let src: &[u8] = bytemuck::cast_slice(plane);
+		dst.get_mut(..src.len()).ok_or(Error::OutOfRange)?.copy_from_slice(src);
+
+		Ok(())
+	}
+
+	pub fn close(&self) {}
+}
+
+pub(crate) struct AudioEncoder {
+	output: AudioEncoderOutput,
+	codec: String,
+
+	// Whether the next frame should include the decoder config.
+	configured: bool,
+}
+
+impl AudioEncoder {
+	pub fn new(config: &AudioEncoderConfig, output: AudioEncoderOutput) -> Self {
+		Self {
+			output,
+			codec: config.codec.clone(),
+			configured: false,
+		}
+	}
+
+	pub fn encode(&mut self, data: &AudioData) {
+		let mut payload = BytesMut::with_capacity(HEADER_SIZE + data.data.len() * size_of::<f32>());
+		payload.put_u32_le(data.channels as u32);
+		payload.put_u32_le(data.frames as u32);
+		payload.put_u32_le(data.sample_rate);
+
+		for sample in &data.data {
+			payload.put_f32_le(*sample);
+		}
+
+		let config = match self.configured {
+			true => None,
+			false => Some(AudioDecoderConfig::new(
+				self.codec.clone(),
+				data.channels as u32,
+				data.sample_rate,
+			)),
+		};
+		self.configured = true;
+
+		// Every audio frame is independently decodable.
+		let frame = EncodedFrame {
+			payload: payload.freeze().into(),
+			timestamp: data.timestamp,
+			keyframe: true,
+			temporal_layer: None,
+			alpha: None,
+			config_changed: false,
+		};
+
+		self.output.frame(frame, config);
+	}
+
+	pub fn close(&self) -> Result<()> {
+		Ok(())
+	}
+}
+
+pub(crate) struct AudioDecoder {
+	frames: mpsc::UnboundedSender<crate::AudioData>,
+	closed: watch::Sender<Result<()>>,
+
+	// Set after configuring or flushing, like WebCodecs.
+	keyframe_required: Cell<bool>,
+}
+
+impl AudioDecoder {
+	pub fn new(frames: mpsc::UnboundedSender<crate::AudioData>, closed: watch::Sender<Result<()>>) -> Self {
+		Self {
+			frames,
+			closed,
+			keyframe_required: Cell::new(true),
+		}
+	}
+
+	pub fn decode(&self, frame: EncodedFrame) -> Result<()> {
+		self.closed.borrow().clone()?;
+
+		if self.keyframe_required.get() && !frame.keyframe {
+			return Err(Error::KeyframeRequired);
+		}
+		self.keyframe_required.set(false);
+
+		// Like WebCodecs, a corrupt payload is reported via the error callback.
+		let data = match Self::decode_payload(frame) {
+			Ok(data) => data,
+			Err(err) => {
+				self.closed.send_replace(Err(err)).ok();
+				return Ok(());
+			}
+		};
+
+		if self.frames.send(data.into()).is_err() {
+			self.closed.send_replace(Err(Error::Dropped)).ok();
+		}
+
+		Ok(())
+	}
+
+	fn decode_payload(frame: EncodedFrame) -> Result<AudioData> {
+		let mut payload = frame.payload.into_bytes();
+		if payload.len() < HEADER_SIZE {
+			return Err(Error::InvalidPayload);
+		}
+
+		let channels = payload.get_u32_le() as usize;
+		let frames = payload.get_u32_le() as usize;
+		let sample_rate = payload.get_u32_le();
+
+		if payload.len() != channels * frames * size_of::<f32>() {
+			return Err(Error::InvalidPayload);
+		}
+
+		let mut data = Vec::with_capacity(channels * frames);
+		while payload.has_remaining() {
+			data.push(payload.get_f32_le());
+		}
+
+		Ok(AudioData {
+			sample_rate,
+			channels,
+			frames,
+			timestamp: frame.timestamp,
+			data,
+		})
+	}
+
+	pub fn flush(&self) {
+		self.keyframe_required.set(true);
+	}
+
+	pub fn close(&self) -> Result<()> {
+		Ok(())
+	}
+}
@@ -0,0 +1,74 @@
+use std::io::Cursor;
+
+use crate::{Error, ImageDecoderConfig, ImageFrame, ImageSource, ImageTrack, Result, VideoFrame, Y4mReader};
+
+/// The only "image" format supported by the mock, since we already have a reader for it.
+const MIME_TYPE: &str = "video/x-yuv4mpeg";
+
+/// Decodes a Y4M file as an animated image with a single track, used in place of [web_sys::ImageDecoder].
+///
+/// The file is decoded up front, so it's always complete.
+pub struct ImageDecoder {
+	frames: Vec<VideoFrame>,
+}
+
+impl ImageDecoder {
+	pub(crate) fn new(config: &ImageDecoderConfig) -> Result<Self> {
+		if config.mime_type != MIME_TYPE {
+			return Err(Error::UnsupportedFormat);
+		}
+
+		// Reading a ReadableStream requires a browser.
+		let ImageSource::Bytes(data) = &config.source else {
+			return Err(Error::UnsupportedFormat);
+		};
+
+		let mut reader = Y4mReader::new(Cursor::new(data))?;
+		let duration = reader.header().timestamp(1);
+
+		let mut frames = Vec::new();
+		while let Some(mut frame) = reader.read()? {
+			frame.set_duration(duration);
+			frames.push(frame);
+		}
+
+		Ok(Self { frames })
+	}
+
+	pub async fn is_type_supported(mime_type: &str) -> Result<bool> {
+		Ok(mime_type == MIME_TYPE)
+	}
+
+	pub async fn tracks(&self) -> Result<Vec<ImageTrack>> {
+		Ok(vec![ImageTrack {
+			animated: self.frames.len() > 1,
+			frame_count: self.frames.len() as u32,
+			repetition_count: Some(0),
+			selected: true,
+		}])
+	}
+
+	pub async fn select_track(&self, index: usize) -> Result<()> {
+		match index {
+			0 => Ok(()),
+			_ => Err(Error::OutOfRange),
+		}
+	}
+
+	pub async fn decode(&self, index: u32) -> Result<ImageFrame> {
+		let frame = self.frames.get(index as usize).ok_or(Error::OutOfRange)?;
+
+		Ok(ImageFrame {
+			frame: frame.clone(),
+			complete: true,
+		})
+	}
+
+	pub fn complete(&self) -> bool {
+		true
+	}
+
+	pub async fn completed(&self) -> Result<()> {
+		Ok(())
+	}
+}
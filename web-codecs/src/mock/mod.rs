@@ -0,0 +1,356 @@
+//! A native backend that replaces WebCodecs, enabled with the `mock` feature on non-wasm32 targets.
+//!
+//! The "codec" is lossless: each [crate::EncodedFrame] is a small header followed by the raw samples.
+//! The first frame after configuration is a keyframe and carries a synthetic decoder config.
+//! The [ImageDecoder] only supports Y4M files (`video/x-yuv4mpeg`), treating each frame as part of an animation.
+//! This is enough to test pipelines with `cargo test` outside of a browser.
+mod audio;
+mod image;
+mod video;
+
+pub use audio::*;
+pub use image::*;
+pub use video::*;
+
+#[cfg(test)]
+mod test {
+	use std::time::Duration;
+
+	use bytes::Bytes;
+
+	use crate::*;
+
+	fn frame(timestamp: Duration) -> VideoFrame {
+		// A 4x2 I420 frame: 8 luma bytes, 2 U bytes, 2 V bytes.
+		let data: Vec<u8> = (0..12).collect();
+		VideoFrame::new(&data, VideoPixelFormat::I420, Dimensions::new(4, 2), timestamp).unwrap()
+	}
+
+	#[tokio::test]
+	async fn video_roundtrip() {
+		let config = VideoEncoderConfig::new("vp8", Dimensions::new(4, 2));
+		let (mut encoder, mut encoded) = config.init().unwrap();
+
+		assert!(encoded.config().is_none());
+
+		for i in 0..3 {
+			encoder
+				.encode(&frame(Duration::from_millis(i * 33)), Default::default())
+				.unwrap();
+		}
+		drop(encoder);
+
+		let config = encoded.config().unwrap();
+		assert_eq!(config.codec, "vp8");
+		assert_eq!(config.resolution, Some(Dimensions::new(4, 2)));
+
+		let (decoder, mut decoded) = config.build().unwrap();
+
+		let mut keyframes = Vec::new();
+		while let Some(frame) = encoded.frame().await.unwrap() {
+			keyframes.push(frame.keyframe);
+			decoder.decode(frame).unwrap();
+		}
+		assert_eq!(keyframes, [true, false, false]);
+		drop(decoder);
+
+		let mut count = 0;
+		while let Some(frame) = decoded.next().await.unwrap() {
+			assert_eq!(frame.timestamp(), Duration::from_millis(count * 33));
+			assert_eq!(frame.dimensions(), Dimensions::new(4, 2));
+			assert_eq!(frame.format(), Some(VideoPixelFormat::I420));
+
+			let mut data = vec![0; frame.allocation_size().unwrap()];
+			frame.copy_to(&mut data).await.unwrap();
+			assert_eq!(data, (0..12).collect::<Vec<u8>>());
+
+			count += 1;
+		}
+		assert_eq!(count, 3);
+	}
+
+	#[tokio::test]
+	async fn video_gop() {
+		let mut config = VideoEncoderConfig::new("vp8", Dimensions::new(4, 2));
+		config.max_gop_duration = Some(Duration::from_secs(1));
+
+		let (mut encoder, mut encoded) = config.init().unwrap();
+
+		// 3 seconds at 4 fps
+		for i in 0..12 {
+			let options = VideoEncodeOptions {
+				// Force a keyframe in the middle of the second GOP.
+				key_frame: (i == 6).then_some(true),
+			};
+			encoder.encode(&frame(Duration::from_millis(i * 250)), options).unwrap();
+		}
+		drop(encoder);
+
+		let mut keyframes = Vec::new();
+		while let Some(frame) = encoded.frame().await.unwrap() {
+			if frame.keyframe {
+				keyframes.push(frame.timestamp.as_millis());
+			}
+		}
+
+		assert_eq!(keyframes, [0, 1000, 1500, 2500]);
+	}
+
+	#[tokio::test]
+	async fn video_reconfigure() {
+		let config = VideoEncoderConfig::new("vp8", Dimensions::new(4, 2));
+		let (mut encoder, mut encoded) = config.clone().init().unwrap();
+
+		encoder.encode(&frame(Duration::ZERO), Default::default()).unwrap();
+		encoder
+			.encode(&frame(Duration::from_millis(33)), Default::default())
+			.unwrap();
+
+		// Changing the bitrate doesn't change the decoder config or force a keyframe.
+		let mut config = config;
+		config.bitrate = Some(1_000_000);
+		encoder.reconfigure(config.clone()).unwrap();
+		encoder
+			.encode(&frame(Duration::from_millis(66)), Default::default())
+			.unwrap();
+
+		// Changing the resolution does both.
+		config.resolution = Dimensions::new(2, 2);
+		encoder.reconfigure(config).unwrap();
+
+		let data = [0; 6];
+		let small = VideoFrame::new(
+			&data,
+			VideoPixelFormat::I420,
+			Dimensions::new(2, 2),
+			Duration::from_millis(99),
+		)
+		.unwrap();
+		encoder.encode(&small, Default::default()).unwrap();
+		drop(encoder);
+
+		let mut keyframes = Vec::new();
+		let mut changed = Vec::new();
+		while let Some(frame) = encoded.frame().await.unwrap() {
+			keyframes.push(frame.keyframe);
+			changed.push(frame.config_changed);
+		}
+
+		assert_eq!(keyframes, [true, false, false, true]);
+		assert_eq!(changed, [true, false, false, true]);
+		assert_eq!(encoded.config().unwrap().resolution, Some(Dimensions::new(2, 2)));
+	}
+
+	#[tokio::test]
+	async fn video_keyframe_required() {
+		let config = VideoDecoderConfig::new("vp8");
+		let (decoder, mut decoded) = config.build().unwrap();
+
+		let delta = EncodedFrame {
+			payload: Bytes::new().into(),
+			timestamp: Duration::ZERO,
+			keyframe: false,
+			temporal_layer: None,
+			alpha: None,
+			config_changed: false,
+		};
+		assert!(matches!(decoder.decode(delta), Err(Error::KeyframeRequired)));
+
+		// A corrupt payload is reported asynchronously.
+		let corrupt = EncodedFrame {
+			payload: Bytes::from_static(b"corrupt").into(),
+			timestamp: Duration::ZERO,
+			keyframe: true,
+			temporal_layer: None,
+			alpha: None,
+			config_changed: false,
+		};
+		decoder.decode(corrupt).unwrap();
+		assert!(matches!(decoded.next().await, Err(Error::InvalidPayload)));
+	}
+
+	#[tokio::test]
+	async fn video_resilient() {
+		let config = VideoEncoderConfig::new("vp8", Dimensions::new(4, 2));
+		let (mut encoder, mut encoded) = config.init().unwrap();
+
+		for i in 0..2 {
+			let options = VideoEncodeOptions { key_frame: Some(true) };
+			encoder.encode(&frame(Duration::from_millis(i * 33)), options).unwrap();
+			encoder
+				.encode(&frame(Duration::from_millis(i * 33 + 11)), Default::default())
+				.unwrap();
+		}
+		drop(encoder);
+
+		let mut config = encoded.config().unwrap();
+		config.resilient = true;
+
+		let (decoder, mut decoded) = config.build().unwrap();
+
+		let mut frames = Vec::new();
+		while let Some(frame) = encoded.frame().await.unwrap() {
+			frames.push(frame);
+		}
+
+		let mut frames = frames.into_iter();
+		decoder.decode(frames.next().unwrap()).unwrap();
+
+		// The corrupt frame kills the decoder, which is recreated on the next decode.
+		let corrupt = EncodedFrame {
+			payload: Bytes::from_static(b"corrupt").into(),
+			timestamp: Duration::from_millis(5),
+			keyframe: false,
+			temporal_layer: None,
+			alpha: None,
+			config_changed: false,
+		};
+		decoder.decode(corrupt).unwrap();
+
+		// The delta frame is skipped until the next keyframe.
+		for frame in frames {
+			decoder.decode(frame).unwrap();
+		}
+		drop(decoder);
+
+		let mut events = Vec::new();
+		while let Some(event) = decoded.next_event().await.unwrap() {
+			events.push(match event {
+				VideoDecodedEvent::Frame(frame) => Ok(frame.timestamp().as_millis()),
+				VideoDecodedEvent::Recovered(err) => Err(err),
+			});
+		}
+
+		assert!(matches!(
+			events.as_slice(),
+			[Ok(0), Err(Error::InvalidPayload), Ok(33), Ok(44)]
+		));
+	}
+
+	#[tokio::test]
+	async fn audio_roundtrip() {
+		let left = [0.0, 0.25, 0.5, 0.75];
+		let right = [-0.0, -0.25, -0.5, -0.75];
+
+		let config = AudioEncoderConfig::new("opus");
+		let (mut encoder, mut encoded) = config.init().unwrap();
+
+		let data = AudioData::new([&left[..], &right[..]].into_iter(), 48_000, Duration::from_millis(10)).unwrap();
+		assert_eq!(data.number_of_channels(), 2);
+		assert_eq!(data.number_of_frames(), 4);
+
+		encoder.encode(&data).unwrap();
+		drop(encoder);
+
+		let config = encoded.config().unwrap();
+		assert_eq!(config.channel_count, 2);
+		assert_eq!(config.sample_rate, 48_000);
+
+		let (decoder, mut decoded) = config.build().unwrap();
+		decoder.decode(encoded.frame().await.unwrap().unwrap()).unwrap();
+		drop(decoder);
+
+		let data = decoded.next().await.unwrap().unwrap();
+		assert_eq!(data.timestamp(), Duration::from_millis(10));
+		assert_eq!(data.sample_rate(), 48_000);
+
+		let mut samples = Vec::new();
+		data.append_to(&mut samples, 0, Default::default()).unwrap();
+		data.append_to(&mut samples, 1, AudioCopyOptions { offset: 2, count: None })
+			.unwrap();
+		assert_eq!(samples, [0.0, 0.25, 0.5, 0.75, -0.5, -0.75]);
+
+		let mut buffer = [0.0; 1];
+		assert!(matches!(
+			data.copy_to(&mut buffer[..], 2, Default::default()),
+			Err(Error::OutOfRange)
+		));
+	}
+
+	#[tokio::test]
+	async fn video_svc() {
+		let mut config = VideoEncoderConfig::new("vp8", Dimensions::new(4, 2));
+		config.scalability_mode = Some(ScalabilityMode::L1T3);
+
+		let (mut encoder, mut encoded) = config.init().unwrap();
+
+		for i in 0..8 {
+			encoder
+				.encode(&frame(Duration::from_millis(i * 33)), Default::default())
+				.unwrap();
+		}
+		drop(encoder);
+
+		// Forward half the frame rate by dropping the top layer.
+		let mut filter = TemporalLayerFilter::new(1);
+		let (decoder, mut decoded) = encoded.config().unwrap().build().unwrap();
+
+		let mut layers = Vec::new();
+		while let Some(frame) = encoded.frame().await.unwrap() {
+			layers.push(frame.temporal_layer.unwrap());
+
+			if let Some(frame) = filter.filter(frame) {
+				decoder.decode(frame).unwrap();
+			}
+		}
+		assert_eq!(layers, [0, 2, 1, 2, 0, 2, 1, 2]);
+		drop(decoder);
+
+		let mut timestamps = Vec::new();
+		while let Some(frame) = decoded.next().await.unwrap() {
+			timestamps.push(frame.timestamp().as_millis());
+		}
+		assert_eq!(timestamps, [0, 66, 132, 198]);
+	}
+
+	#[tokio::test]
+	async fn image_decode() {
+		let header = Y4mHeader {
+			dimensions: Dimensions::new(4, 2),
+			format: VideoPixelFormat::I420,
+			framerate: (10, 1),
+		};
+
+		let mut writer = Y4mWriter::new(Vec::new(), header).unwrap();
+		for i in 0..3 {
+			writer.write(&frame(Duration::from_millis(i * 100))).await.unwrap();
+		}
+		let data = writer.into_inner();
+
+		assert!(crate::ImageDecoder::is_type_supported("video/x-yuv4mpeg")
+			.await
+			.unwrap());
+		assert!(!crate::ImageDecoder::is_type_supported("image/png").await.unwrap());
+
+		let config = ImageDecoderConfig::new(data, "video/x-yuv4mpeg");
+		let (decoder, mut decoded) = config.build().unwrap();
+
+		let tracks = decoder.tracks().await.unwrap();
+		assert_eq!(
+			tracks,
+			[ImageTrack {
+				animated: true,
+				frame_count: 3,
+				repetition_count: Some(0),
+				selected: true,
+			}]
+		);
+
+		let mut timestamps = Vec::new();
+		while let Some(image) = decoded.next().await.unwrap() {
+			assert!(image.complete);
+			assert_eq!(image.frame.duration(), Some(Duration::from_millis(100)));
+			timestamps.push(image.frame.timestamp().as_millis());
+		}
+		assert_eq!(timestamps, [0, 100, 200]);
+
+		let image = decoder.decode(1).await.unwrap();
+		assert_eq!(image.frame.timestamp(), Duration::from_millis(100));
+		assert!(matches!(decoder.decode(3).await, Err(Error::OutOfRange)));
+
+		// Selecting a track restarts from the first frame.
+		decoder.select_track(0).await.unwrap();
+		let image = decoded.next().await.unwrap().unwrap();
+		assert_eq!(image.frame.timestamp(), Duration::ZERO);
+	}
+}
@@ -0,0 +1,257 @@
+use std::{cell::Cell, time::Duration};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio::sync::{mpsc, watch};
+
+use crate::{
+	allocation_size, Dimensions, EncodedFrame, Error, Result, ScalabilityMode, Timestamp, VideoDecodedEvent,
+	VideoDecoderConfig, VideoEncoderConfig, VideoEncoderOutput, VideoPixelFormat,
+};
+
+// The index is used to identify the format in the payload.
+const FORMATS: [VideoPixelFormat; 9] = [
+	VideoPixelFormat::I420,
+	VideoPixelFormat::I420a,
+	VideoPixelFormat::I422,
+	VideoPixelFormat::I444,
+	VideoPixelFormat::Nv12,
+	VideoPixelFormat::Rgba,
+	VideoPixelFormat::Rgbx,
+	VideoPixelFormat::Bgra,
+	VideoPixelFormat::Bgrx,
+];
+
+// format (u8) + width (u32) + height (u32)
+const HEADER_SIZE: usize = 9;
+
+/// A raw video frame stored in memory, used in place of [web_sys::VideoFrame].
+///
+/// The getters mirror [web_sys::VideoFrame] so [crate::VideoFrame] can dereference to either.
+#[derive(Debug, Clone)]
+pub struct VideoFrame {
+	format: VideoPixelFormat,
+	dimensions: Dimensions,
+	timestamp: Timestamp,
+	duration: Option<Duration>,
+	data: Bytes,
+}
+
+impl VideoFrame {
+	pub(crate) fn new(
+		data: &[u8],
+		format: VideoPixelFormat,
+		dimensions: Dimensions,
+		timestamp: Timestamp,
+	) -> Result<Self> {
+		if dimensions.width == 0 || dimensions.height == 0 {
+			return Err(Error::InvalidDimensions);
+		}
+
+		let size = allocation_size(format, dimensions).ok_or(Error::UnsupportedFormat)?;
+		let data = data.get(..size).ok_or(Error::OutOfRange)?;
+
+		Ok(Self {
+			format,
+			dimensions,
+			timestamp,
+			duration: None,
+			data: Bytes::copy_from_slice(data),
+		})
+	}
+
+	pub fn format(&self) -> Option<VideoPixelFormat> {
+		Some(self.format)
+	}
+
+	pub fn coded_width(&self) -> u32 {
+		self.dimensions.width
+	}
+
+	pub fn coded_height(&self) -> u32 {
+		self.dimensions.height
+	}
+
+	/// The timestamp in microseconds.
+	pub fn timestamp(&self) -> Option<f64> {
+		Some(self.timestamp.as_micros() as _)
+	}
+
+	/// The duration in microseconds.
+	pub fn duration(&self) -> Option<f64> {
+		Some(self.duration?.as_micros() as _)
+	}
+
+	pub(crate) fn set_duration(&mut self, duration: Duration) {
+		self.duration = Some(duration);
+	}
+
+	pub fn allocation_size(&self) -> usize {
+		self.data.len()
+	}
+
+	/// The tightly packed pixel data.
+	pub fn as_bytes(&self) -> &[u8] {
+		&self.data
+	}
+
+	pub fn close(&self) {}
+}
+
+pub(crate) struct VideoEncoder {
+	output: VideoEncoderOutput,
+
+	// The config to attach to the next frame, which is always a keyframe.
+	decoder_config: Option<VideoDecoderConfig>,
+
+	// The config that was last emitted.
+	current: VideoDecoderConfig,
+
+	scalability_mode: Option<ScalabilityMode>,
+
+	// The number of frames since the last keyframe, used to assign temporal layers.
+	index: u64,
+}
+
+impl VideoEncoder {
+	pub fn new(config: &VideoEncoderConfig, output: VideoEncoderOutput) -> Self {
+		let decoder_config = Self::decoder_config(config);
+
+		Self {
+			output,
+			current: decoder_config.clone(),
+			decoder_config: Some(decoder_config),
+			scalability_mode: config.scalability_mode,
+			index: 0,
+		}
+	}
+
+	// Like WebCodecs, only emit a new decoder config if it changed.
+	pub fn configure(&mut self, config: &VideoEncoderConfig) {
+		let decoder_config = Self::decoder_config(config);
+
+		if decoder_config.codec != self.current.codec || decoder_config.resolution != self.current.resolution {
+			self.decoder_config = Some(decoder_config.clone());
+		}
+
+		self.current = decoder_config;
+		self.scalability_mode = config.scalability_mode;
+	}
+
+	fn decoder_config(config: &VideoEncoderConfig) -> VideoDecoderConfig {
+		VideoDecoderConfig {
+			codec: config.codec.clone(),
+			resolution: Some(config.resolution),
+			display: config.display,
+			latency_optimized: config.latency_optimized,
+			..Default::default()
+		}
+	}
+
+	pub fn encode(&mut self, frame: &VideoFrame, key_frame: bool) {
+		let format = FORMATS.iter().position(|f| *f == frame.format).unwrap();
+
+		let mut payload = BytesMut::with_capacity(HEADER_SIZE + frame.data.len());
+		payload.put_u8(format as u8);
+		payload.put_u32_le(frame.dimensions.width);
+		payload.put_u32_le(frame.dimensions.height);
+		payload.put_slice(&frame.data);
+
+		let decoder_config = self.decoder_config.take();
+		let keyframe = key_frame || decoder_config.is_some();
+
+		if keyframe {
+			self.index = 0;
+		}
+
+		let frame = EncodedFrame {
+			payload: payload.freeze().into(),
+			timestamp: frame.timestamp,
+			keyframe,
+			temporal_layer: self.scalability_mode.map(|mode| mode.temporal_layer(self.index)),
+			alpha: None,
+			config_changed: false,
+		};
+
+		self.index += 1;
+
+		self.output.frame(frame, decoder_config);
+	}
+
+	pub fn close(&self) -> Result<()> {
+		Ok(())
+	}
+}
+
+pub(crate) struct VideoDecoder {
+	frames: mpsc::UnboundedSender<VideoDecodedEvent>,
+	closed: watch::Sender<Result<()>>,
+
+	// Set after configuring or flushing, like WebCodecs.
+	keyframe_required: Cell<bool>,
+}
+
+impl VideoDecoder {
+	pub fn new(frames: mpsc::UnboundedSender<VideoDecodedEvent>, closed: watch::Sender<Result<()>>) -> Self {
+		Self {
+			frames,
+			closed,
+			keyframe_required: Cell::new(true),
+		}
+	}
+
+	pub fn decode(&self, frame: EncodedFrame) -> Result<()> {
+		self.closed.borrow().clone()?;
+
+		if self.keyframe_required.get() && !frame.keyframe {
+			return Err(Error::KeyframeRequired);
+		}
+		self.keyframe_required.set(false);
+
+		// Like WebCodecs, a corrupt payload is reported via the error callback.
+		let frame = match Self::decode_payload(frame) {
+			Ok(frame) => frame,
+			Err(err) => {
+				self.closed.send_replace(Err(err)).ok();
+				return Ok(());
+			}
+		};
+
+		if self.frames.send(VideoDecodedEvent::Frame(frame.into())).is_err() {
+			self.closed.send_replace(Err(Error::Dropped)).ok();
+		}
+
+		Ok(())
+	}
+
+	fn decode_payload(frame: EncodedFrame) -> Result<VideoFrame> {
+		let mut payload = frame.payload.into_bytes();
+		if payload.len() < HEADER_SIZE {
+			return Err(Error::InvalidPayload);
+		}
+
+		let format = *FORMATS.get(payload.get_u8() as usize).ok_or(Error::InvalidPayload)?;
+		let width = payload.get_u32_le();
+		let height = payload.get_u32_le();
+
+		let dimensions = Dimensions { width, height };
+		if allocation_size(format, dimensions) != Some(payload.len()) {
+			return Err(Error::InvalidPayload);
+		}
+
+		Ok(VideoFrame {
+			format,
+			dimensions,
+			timestamp: frame.timestamp,
+			duration: None,
+			data: payload,
+		})
+	}
+
+	pub fn flush(&self) {
+		self.keyframe_required.set(true);
+	}
+
+	pub fn close(&self) -> Result<()> {
+		Ok(())
+	}
+}
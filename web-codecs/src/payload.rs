@@ -0,0 +1,590 @@
+use std::{collections::VecDeque, fmt, ops::Deref};
+
+#[cfg(target_arch = "wasm32")]
+use std::{
+	cell::{OnceCell, RefCell},
+	rc::Rc,
+};
+
+use bytes::{Bytes, BytesMut};
+
+#[cfg(not(mock))]
+use wasm_bindgen::{JsCast, JsValue};
+
+/// The payload of an [crate::EncodedFrame], either Rust [Bytes] or the original JS object.
+///
+/// Encoder output keeps the JS chunk and only copies it into WASM memory when the bytes are first accessed,
+/// ex. via [Self::bytes] or by dereferencing, using a buffer from a per-thread [BufferPool]. Passing the payload
+/// back into a decoder never copies it through WASM memory: the original chunk is reused if the timestamp and
+/// type match, otherwise it's copied once within JS. Cloning is cheap in both cases.
+///
+/// JS objects can't be sent between threads, so native builds copy them into [Bytes] immediately.
+#[derive(Clone)]
+pub struct EncodedPayload(Inner);
+
+#[derive(Clone)]
+enum Inner {
+	Bytes(Bytes),
+	#[cfg(target_arch = "wasm32")]
+	Js(Rc<JsPayload>),
+}
+
+#[cfg(target_arch = "wasm32")]
+struct JsPayload {
+	source: JsSource,
+	size: usize,
+
+	// Populated the first time the bytes are accessed.
+	bytes: OnceCell<Bytes>,
+}
+
+enum JsSource {
+	Video(web_sys::EncodedVideoChunk),
+	Audio(web_sys::EncodedAudioChunk),
+	Buffer(js_sys::Uint8Array),
+}
+
+#[cfg(target_arch = "wasm32")]
+thread_local! {
+	// Used to materialize a payload when the caller doesn't provide a pool.
+	static POOL: RefCell<BufferPool> = RefCell::default();
+}
+
+impl EncodedPayload {
+	#[cfg(target_arch = "wasm32")]
+	fn js(source: JsSource, size: usize) -> Self {
+		Self(Inner::Js(Rc::new(JsPayload {
+			source,
+			size,
+			bytes: OnceCell::new(),
+		})))
+	}
+
+	#[cfg(not(target_arch = "wasm32"))]
+	fn js(source: JsSource, size: usize) -> Self {
+		Self(Inner::Bytes(source.copy(size, BytesMut::new())))
+	}
+
+	pub fn len(&self) -> usize {
+		match &self.0 {
+			Inner::Bytes(bytes) => bytes.len(),
+			#[cfg(target_arch = "wasm32")]
+			Inner::Js(js) => js.size,
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Returns true if the payload is stored in WASM memory, so accessing it won't copy.
+	pub fn is_materialized(&self) -> bool {
+		self.materialized().is_some()
+	}
+
+	fn materialized(&self) -> Option<&Bytes> {
+		match &self.0 {
+			Inner::Bytes(bytes) => Some(bytes),
+			#[cfg(target_arch = "wasm32")]
+			Inner::Js(js) => js.bytes.get(),
+		}
+	}
+
+	/// Returns the payload as [Bytes], copying it out of JS on first access.
+	pub fn bytes(&self) -> Bytes {
+		match &self.0 {
+			Inner::Bytes(bytes) => bytes.clone(),
+			#[cfg(target_arch = "wasm32")]
+			Inner::Js(js) => js.materialize().clone(),
+		}
+	}
+
+	/// Like [Self::bytes], but copies into a buffer from the given pool instead of the per-thread one.
+	#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+	pub fn bytes_pooled(&self, pool: &mut BufferPool) -> Bytes {
+		match &self.0 {
+			Inner::Bytes(bytes) => bytes.clone(),
+			#[cfg(target_arch = "wasm32")]
+			Inner::Js(js) => js.materialize_with(pool).clone(),
+		}
+	}
+
+	pub fn into_bytes(self) -> Bytes {
+		self.bytes()
+	}
+
+	/// Create a chunk for a decoder, avoiding a copy through WASM memory.
+	#[cfg(not(mock))]
+	pub(crate) fn chunk<C: DecoderChunk>(
+		&self,
+		timestamp: crate::Timestamp,
+		chunk_type: C::Type,
+	) -> Result<C, JsValue> {
+		let timestamp = timestamp.as_micros() as f64;
+
+		#[cfg(target_arch = "wasm32")]
+		if let Inner::Js(js) = &self.0 {
+			if let Some(chunk) = js.source.value().dyn_ref::<C>() {
+				if chunk.matches(timestamp, chunk_type) {
+					return Ok(chunk.clone());
+				}
+			}
+
+			// Once materialized, creating the chunk from WASM memory avoids a copy within JS.
+			if js.bytes.get().is_none() {
+				if let JsSource::Buffer(buffer) = &js.source {
+					return C::create(buffer, timestamp, chunk_type, false);
+				}
+
+				// Nothing else references the copy, so it's transferred to the chunk instead of copied again.
+				let buffer = js_sys::Uint8Array::new_with_length(js.size as _);
+				js.source.copy_js(&buffer)?;
+				return C::create(&buffer, timestamp, chunk_type, true);
+			}
+		}
+
+		let bytes = self.materialized().expect("payload is materialized");
+
+		// SAFETY: The view is only passed to the chunk constructor, which copies it synchronously.
+		// Nothing in between allocates, so WASM memory can't grow and detach the view.
+		let view = unsafe { js_sys::Uint8Array::view(bytes) };
+		C::create(&view, timestamp, chunk_type, false)
+	}
+}
+
+#[cfg(target_arch = "wasm32")]
+impl JsPayload {
+	fn materialize(&self) -> &Bytes {
+		self.bytes.get_or_init(|| POOL.with_borrow_mut(|pool| self.copy(pool)))
+	}
+
+	fn materialize_with(&self, pool: &mut BufferPool) -> &Bytes {
+		self.bytes.get_or_init(|| self.copy(pool))
+	}
+
+	fn copy(&self, pool: &mut BufferPool) -> Bytes {
+		pool.alloc(self.size, |buffer| self.source.copy(self.size, buffer))
+	}
+}
+
+impl JsSource {
+	fn copy(&self, size: usize, mut buffer: BytesMut) -> Bytes {
+		buffer.resize(size, 0);
+
+		match self {
+			Self::Video(chunk) => chunk.copy_to_with_u8_slice(&mut buffer).unwrap(),
+			Self::Audio(chunk) => chunk.copy_to_with_u8_slice(&mut buffer).unwrap(),
+			Self::Buffer(array) => array.copy_to(&mut buffer),
+		}
+
+		buffer.freeze()
+	}
+
+	// Copy into a JS buffer; a JS to JS copy avoids going through WASM memory.
+	#[cfg(target_arch = "wasm32")]
+	fn copy_js(&self, buffer: &js_sys::Uint8Array) -> Result<(), JsValue> {
+		match self {
+			Self::Video(chunk) => chunk.copy_to_with_u8_array(buffer),
+			Self::Audio(chunk) => chunk.copy_to_with_u8_array(buffer),
+			Self::Buffer(array) => {
+				buffer.set(array, 0);
+				Ok(())
+			}
+		}
+	}
+
+	#[cfg(target_arch = "wasm32")]
+	fn value(&self) -> &JsValue {
+		match self {
+			Self::Video(chunk) => chunk.as_ref(),
+			Self::Audio(chunk) => chunk.as_ref(),
+			Self::Buffer(buffer) => buffer.as_ref(),
+		}
+	}
+}
+
+/// An encoded chunk accepted by a decoder, so video and audio can share [EncodedPayload::chunk].
+#[cfg(not(mock))]
+pub(crate) trait DecoderChunk: JsCast + Clone {
+	type Type: Copy + PartialEq;
+
+	// Returns true if the chunk can be decoded as is. Natively, there are never any chunks to reuse.
+	#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+	fn matches(&self, timestamp: f64, chunk_type: Self::Type) -> bool;
+
+	// Create a chunk from the data, transferring its buffer if we own it.
+	fn create(
+		data: &js_sys::Uint8Array,
+		timestamp: f64,
+		chunk_type: Self::Type,
+		transfer: bool,
+	) -> Result<Self, JsValue>;
+}
+
+#[cfg(not(mock))]
+impl DecoderChunk for web_sys::EncodedVideoChunk {
+	type Type = web_sys::EncodedVideoChunkType;
+
+	fn matches(&self, timestamp: f64, chunk_type: Self::Type) -> bool {
+		self.timestamp() == timestamp && self.type_() == chunk_type
+	}
+
+	fn create(
+		data: &js_sys::Uint8Array,
+		timestamp: f64,
+		chunk_type: Self::Type,
+		transfer: bool,
+	) -> Result<Self, JsValue> {
+		let init = web_sys::EncodedVideoChunkInit::new(data, timestamp, chunk_type);
+		if transfer {
+			set_transfer(&init, data)?;
+		}
+
+		Self::new(&init)
+	}
+}
+
+#[cfg(not(mock))]
+impl DecoderChunk for web_sys::EncodedAudioChunk {
+	type Type = web_sys::EncodedAudioChunkType;
+
+	fn matches(&self, timestamp: f64, chunk_type: Self::Type) -> bool {
+		self.timestamp() == timestamp && self.type_() == chunk_type
+	}
+
+	fn create(
+		data: &js_sys::Uint8Array,
+		timestamp: f64,
+		chunk_type: Self::Type,
+		transfer: bool,
+	) -> Result<Self, JsValue> {
+		let init = web_sys::EncodedAudioChunkInit::new(data, timestamp, chunk_type);
+		if transfer {
+			set_transfer(&init, data)?;
+		}
+
+		Self::new(&init)
+	}
+}
+
+// Manually add `transfer` to the init options, since web_sys doesn't support it yet.
+// Browsers that don't support it ignore the option and copy instead.
+#[cfg(not(mock))]
+fn set_transfer(init: &JsValue, data: &js_sys::Uint8Array) -> Result<(), JsValue> {
+	let transfer = js_sys::Array::of1(&data.buffer());
+	js_sys::Reflect::set(init, &js_sys::JsString::from("transfer"), &transfer)?;
+	Ok(())
+}
+
+impl Deref for EncodedPayload {
+	type Target = [u8];
+
+	fn deref(&self) -> &Self::Target {
+		match &self.0 {
+			Inner::Bytes(bytes) => bytes,
+			#[cfg(target_arch = "wasm32")]
+			Inner::Js(js) => js.materialize(),
+		}
+	}
+}
+
+impl AsRef<[u8]> for EncodedPayload {
+	fn as_ref(&self) -> &[u8] {
+		self
+	}
+}
+
+impl PartialEq for EncodedPayload {
+	fn eq(&self, other: &Self) -> bool {
+		self.len() == other.len() && **self == **other
+	}
+}
+
+impl Eq for EncodedPayload {}
+
+impl fmt::Debug for EncodedPayload {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("EncodedPayload")
+			.field("len", &self.len())
+			.field("materialized", &self.is_materialized())
+			.finish()
+	}
+}
+
+impl Default for EncodedPayload {
+	fn default() -> Self {
+		Self(Inner::Bytes(Bytes::new()))
+	}
+}
+
+impl From<Bytes> for EncodedPayload {
+	fn from(bytes: Bytes) -> Self {
+		Self(Inner::Bytes(bytes))
+	}
+}
+
+impl From<Vec<u8>> for EncodedPayload {
+	fn from(bytes: Vec<u8>) -> Self {
+		Self(Inner::Bytes(bytes.into()))
+	}
+}
+
+impl From<&'static [u8]> for EncodedPayload {
+	fn from(bytes: &'static [u8]) -> Self {
+		Self(Inner::Bytes(Bytes::from_static(bytes)))
+	}
+}
+
+impl From<web_sys::EncodedVideoChunk> for EncodedPayload {
+	fn from(chunk: web_sys::EncodedVideoChunk) -> Self {
+		let size = chunk.byte_length() as usize;
+		Self::js(JsSource::Video(chunk), size)
+	}
+}
+
+impl From<web_sys::EncodedAudioChunk> for EncodedPayload {
+	fn from(chunk: web_sys::EncodedAudioChunk) -> Self {
+		let size = chunk.byte_length() as usize;
+		Self::js(JsSource::Audio(chunk), size)
+	}
+}
+
+impl From<js_sys::Uint8Array> for EncodedPayload {
+	fn from(buffer: js_sys::Uint8Array) -> Self {
+		let size = buffer.length() as usize;
+		Self::js(JsSource::Buffer(buffer), size)
+	}
+}
+
+/// Reuses allocations for [Bytes] once every reference to them has been dropped.
+///
+/// The pool keeps a reference to each buffer it hands out, up to `capacity`, and checks if it's the last
+/// remaining reference on the next allocation. Buffers keep their initialized length, so reusing a buffer
+/// for [EncodedPayload::bytes_pooled] only zeroes the bytes beyond its previous length.
+#[derive(Debug)]
+pub struct BufferPool {
+	capacity: usize,
+
+	// Buffers handed out, in the order they were allocated.
+	used: VecDeque<Bytes>,
+
+	// Buffers that can be reused.
+	free: Vec<BytesMut>,
+
+	reused: u64,
+	allocated: u64,
+}
+
+impl BufferPool {
+	/// Create a pool that tracks up to `capacity` buffers.
+	pub fn new(capacity: usize) -> Self {
+		Self {
+			capacity,
+			used: VecDeque::with_capacity(capacity),
+			free: Vec::new(),
+			reused: 0,
+			allocated: 0,
+		}
+	}
+
+	/// Allocate a buffer with a capacity of at least `size` bytes, filled by the closure.
+	///
+	/// The buffer may contain up to `size` bytes of stale data from a previous allocation,
+	/// so the closure should overwrite or clear it.
+	pub fn alloc<F: FnOnce(BytesMut) -> Bytes>(&mut self, size: usize, fill: F) -> Bytes {
+		self.recycle();
+
+		// Use the smallest buffer that fits.
+		let index = self
+			.free
+			.iter()
+			.enumerate()
+			.filter(|(_, buffer)| buffer.capacity() >= size)
+			.min_by_key(|(_, buffer)| buffer.capacity())
+			.map(|(index, _)| index);
+
+		let mut buffer = match index {
+			Some(index) => {
+				self.reused += 1;
+				self.free.swap_remove(index)
+			}
+			None => {
+				self.allocated += 1;
+				BytesMut::with_capacity(size)
+			}
+		};
+
+		buffer.truncate(size);
+
+		let bytes = fill(buffer);
+
+		if self.used.len() >= self.capacity {
+			self.used.pop_front();
+		}
+
+		if self.capacity > 0 {
+			self.used.push_back(bytes.clone());
+		}
+
+		bytes
+	}
+
+	/// Copy the slice into a buffer from the pool.
+	pub fn copy_from_slice(&mut self, data: &[u8]) -> Bytes {
+		self.alloc(data.len(), |mut buffer| {
+			buffer.clear();
+			buffer.extend_from_slice(data);
+			buffer.freeze()
+		})
+	}
+
+	/// The number of allocations that reused a previous buffer.
+	pub fn reused(&self) -> u64 {
+		self.reused
+	}
+
+	/// The number of allocations that required a new buffer.
+	pub fn allocated(&self) -> u64 {
+		self.allocated
+	}
+
+	// Move any buffers that are no longer referenced elsewhere to the free list.
+	fn recycle(&mut self) {
+		for _ in 0..self.used.len() {
+			let bytes = self.used.pop_front().unwrap();
+
+			match bytes.try_into_mut() {
+				Ok(buffer) if self.free.len() < self.capacity => self.free.push(buffer),
+				Ok(_) => {}
+				Err(bytes) => self.used.push_back(bytes),
+			}
+		}
+	}
+}
+
+impl Default for BufferPool {
+	fn default() -> Self {
+		Self::new(32)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn payload() {
+		let payload = EncodedPayload::from(Bytes::from_static(b"hello"));
+		assert_eq!(payload.len(), 5);
+		assert!(payload.is_materialized());
+		assert_eq!(&*payload, b"hello");
+		assert_eq!(payload, EncodedPayload::from(b"hello".to_vec()));
+		assert!(EncodedPayload::default().is_empty());
+	}
+
+	#[test]
+	fn pool() {
+		let mut pool = BufferPool::new(4);
+
+		let a = pool.copy_from_slice(&[1; 100]);
+		let b = pool.copy_from_slice(&[2; 50]);
+		assert_eq!(pool.allocated(), 2);
+
+		// Still referenced, so a new buffer is needed.
+		let c = pool.copy_from_slice(&[3; 10]);
+		assert_eq!(pool.allocated(), 3);
+		assert_eq!(pool.reused(), 0);
+
+		let ptr = a.as_ptr();
+		drop(a);
+		drop(b);
+
+		// The smallest buffer that fits is reused.
+		let d = pool.copy_from_slice(&[4; 80]);
+		assert_eq!(d.as_ptr(), ptr);
+		assert_eq!(d, [4; 80].as_slice());
+		assert_eq!(pool.reused(), 1);
+
+		let e = pool.copy_from_slice(&[5; 20]);
+		assert_eq!(e, [5; 20].as_slice());
+		assert_eq!(pool.reused(), 2);
+		assert_eq!(c, [3; 10].as_slice());
+	}
+
+	// Native payloads are always Bytes, so frames can be moved between threads.
+	#[cfg(not(target_arch = "wasm32"))]
+	#[test]
+	fn send() {
+		fn assert_send<T: Send>() {}
+		assert_send::<EncodedPayload>();
+		assert_send::<crate::EncodedFrame>();
+	}
+}
+
+// Compares the previous decode path, which copied each chunk into WASM memory and back out again, against passing
+// the payload through. Run in a browser with `wasm-pack test --headless --chrome web-codecs`.
+#[cfg(all(test, target_arch = "wasm32"))]
+mod bench {
+	use std::time::Duration;
+
+	use wasm_bindgen_test::{console_log, wasm_bindgen_test};
+	use web_sys::{EncodedVideoChunk, EncodedVideoChunkInit, EncodedVideoChunkType};
+
+	use super::*;
+
+	// Roughly a 4K keyframe at 20 Mb/s.
+	const SIZE: u32 = 1_000_000;
+	const FRAMES: u32 = 100;
+
+	fn run(name: &str, mut decode: impl FnMut(&EncodedVideoChunk) -> EncodedVideoChunk) -> f64 {
+		let data = js_sys::Uint8Array::new_with_length(SIZE);
+		data.fill(0xAB, 0, SIZE);
+		let chunk =
+			EncodedVideoChunk::new(&EncodedVideoChunkInit::new(&data, 0.0, EncodedVideoChunkType::Key)).unwrap();
+
+		let start = js_sys::Date::now();
+		for _ in 0..FRAMES {
+			let output = decode(&chunk);
+			assert_eq!(output.byte_length(), SIZE);
+		}
+
+		let elapsed = (js_sys::Date::now() - start) / FRAMES as f64;
+		console_log!("{name}: {elapsed:.3}ms per frame");
+
+		elapsed
+	}
+
+	#[wasm_bindgen_test]
+	fn decode() {
+		// The previous behavior: allocate, zero, copy into WASM memory, then copy back out.
+		let copied = run("copied", |chunk| {
+			let mut buffer = BytesMut::new();
+			buffer.resize(SIZE as usize, 0);
+			chunk.copy_to_with_u8_slice(&mut buffer).unwrap();
+
+			let data = js_sys::Uint8Array::from(buffer.as_ref());
+			let init = EncodedVideoChunkInit::new(&data, chunk.timestamp(), chunk.type_());
+			EncodedVideoChunk::new(&init).unwrap()
+		});
+
+		// Decoding encoder output as is reuses the chunk.
+		let reused = run("reused", |chunk| {
+			let payload = EncodedPayload::from(chunk.clone());
+			payload.chunk(Duration::ZERO, EncodedVideoChunkType::Key).unwrap()
+		});
+
+		// A different timestamp needs a new chunk, copied once within JS.
+		let retimed = run("retimed", |chunk| {
+			let payload = EncodedPayload::from(chunk.clone());
+			payload
+				.chunk(Duration::from_millis(1), EncodedVideoChunkType::Key)
+				.unwrap()
+		});
+
+		console_log!(
+			"reused: {:.1}x faster, retimed: {:.1}x faster",
+			copied / reused,
+			copied / retimed
+		);
+	}
+}
@@ -0,0 +1,326 @@
+use std::time::Duration;
+
+use crate::{Error, Result, Timestamp};
+
+#[derive(Debug, Clone)]
+pub struct PlayoutConfig {
+	/// The delay between receiving the first frame and rendering it, used to absorb network jitter.
+	/// Only applies when anchoring to the wall clock; the audio position is used as-is.
+	pub latency: Duration,
+
+	/// Frames that are later than this are dropped instead of rendered.
+	pub max_late: Duration,
+
+	/// Fall back to the wall clock if the audio position hasn't been updated for this long.
+	pub audio_timeout: Duration,
+}
+
+impl Default for PlayoutConfig {
+	fn default() -> Self {
+		Self {
+			latency: Duration::from_millis(100),
+			max_late: Duration::from_millis(50),
+			audio_timeout: Duration::from_millis(200),
+		}
+	}
+}
+
+/// What to do with a video frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Playout {
+	/// The frame is due, render it now.
+	Render,
+
+	/// The frame is early, try again after the given (wall clock) duration, or None if paused.
+	Hold(Option<Duration>),
+
+	/// The frame is too late to render, skip it.
+	Drop,
+}
+
+/// The source currently driving the [PlayoutClock].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayoutSource {
+	Audio,
+	Wall,
+}
+
+// The media position (in microseconds) at the given wall clock time.
+// The position is signed because the latency can push it before the first timestamp.
+#[derive(Debug, Clone, Copy)]
+struct Anchor {
+	wall: Duration,
+	media: i64,
+}
+
+/// Decides when to render each [crate::VideoFrame], synchronized to audio output.
+///
+/// The clock is driven by the audio output position when available, extrapolating from the latest update,
+/// and otherwise from the wall clock anchored at the first frame. No time source is read directly;
+/// every method takes `now`, a monotonic wall clock time such as `performance.now()`, so it can be simulated.
+#[derive(Debug, Clone)]
+pub struct PlayoutClock {
+	config: PlayoutConfig,
+	anchor: Option<Anchor>,
+	rate: f64,
+	paused: bool,
+
+	// The wall clock time of the last audio update.
+	audio: Option<Duration>,
+}
+
+impl PlayoutClock {
+	pub fn new(config: PlayoutConfig) -> Self {
+		Self {
+			config,
+			anchor: None,
+			rate: 1.0,
+			paused: false,
+			audio: None,
+		}
+	}
+
+	pub fn config(&self) -> &PlayoutConfig {
+		&self.config
+	}
+
+	/// Report the timestamp of the audio currently being output.
+	///
+	/// Ignored while paused, since the audio output should be paused too.
+	pub fn update_audio(&mut self, now: Duration, position: Timestamp) {
+		if self.paused {
+			return;
+		}
+
+		self.anchor = Some(Anchor {
+			wall: now,
+			media: micros(position),
+		});
+		self.audio = Some(now);
+	}
+
+	/// Decide whether the frame with the given timestamp should be rendered, held, or dropped.
+	///
+	/// If the clock hasn't started, it's anchored to this frame plus the latency target.
+	pub fn decide(&mut self, now: Duration, timestamp: Timestamp) -> Playout {
+		let timestamp = micros(timestamp);
+
+		let position = match self.position_micros(now) {
+			Some(position) => position,
+			None => {
+				let anchor = Anchor {
+					wall: now,
+					media: timestamp - micros(self.config.latency),
+				};
+				self.anchor = Some(anchor);
+				anchor.media
+			}
+		};
+
+		let late = position - timestamp;
+		if late > micros(self.config.max_late) {
+			Playout::Drop
+		} else if late >= 0 {
+			Playout::Render
+		} else if self.paused {
+			Playout::Hold(None)
+		} else {
+			Playout::Hold(Some(Duration::from_micros((-late as f64 / self.rate) as u64)))
+		}
+	}
+
+	/// The media timestamp being played at the given time, or None if the clock hasn't started.
+	pub fn position(&self, now: Duration) -> Option<Timestamp> {
+		let position = self.position_micros(now)?;
+		Some(Timestamp::from_micros(position.max(0) as u64))
+	}
+
+	fn position_micros(&self, now: Duration) -> Option<i64> {
+		let anchor = self.anchor?;
+		if self.paused {
+			return Some(anchor.media);
+		}
+
+		let elapsed = micros(now) - micros(anchor.wall);
+		Some(anchor.media + (elapsed as f64 * self.rate) as i64)
+	}
+
+	/// Whether the clock is currently driven by audio or the wall clock.
+	pub fn source(&self, now: Duration) -> PlayoutSource {
+		match self.audio {
+			Some(updated) if now.saturating_sub(updated) <= self.config.audio_timeout => PlayoutSource::Audio,
+			_ => PlayoutSource::Wall,
+		}
+	}
+
+	pub fn rate(&self) -> f64 {
+		self.rate
+	}
+
+	/// Change the playback rate, continuing from the current position.
+	pub fn set_rate(&mut self, now: Duration, rate: f64) -> Result<()> {
+		if !rate.is_finite() || rate <= 0.0 {
+			return Err(Error::OutOfRange);
+		}
+
+		self.reanchor(now);
+		self.rate = rate;
+
+		Ok(())
+	}
+
+	/// Change the latency target, shifting the position if driven by the wall clock.
+	pub fn set_latency(&mut self, now: Duration, latency: Duration) {
+		if self.source(now) == PlayoutSource::Wall {
+			if let Some(anchor) = self.anchor.as_mut() {
+				anchor.media += micros(self.config.latency) - micros(latency);
+			}
+		}
+
+		self.config.latency = latency;
+	}
+
+	pub fn is_paused(&self) -> bool {
+		self.paused
+	}
+
+	/// Freeze the position; frames that are already due are still rendered.
+	pub fn pause(&mut self, now: Duration) {
+		if !self.paused {
+			self.reanchor(now);
+			self.paused = true;
+		}
+	}
+
+	/// Continue from the position when paused.
+	pub fn resume(&mut self, now: Duration) {
+		if self.paused {
+			self.reanchor(now);
+			self.paused = false;
+		}
+	}
+
+	/// Jump to the given timestamp, keeping the paused state.
+	///
+	/// The audio position is ignored until it's updated after the seek.
+	pub fn seek(&mut self, now: Duration, timestamp: Timestamp) {
+		self.anchor = Some(Anchor {
+			wall: now,
+			media: micros(timestamp),
+		});
+		self.audio = None;
+	}
+
+	/// Forget the position, so the clock is anchored to the next frame or audio update.
+	pub fn reset(&mut self) {
+		self.anchor = None;
+		self.audio = None;
+	}
+
+	// Move the anchor to the current position, so rate and pause changes only affect the future.
+	fn reanchor(&mut self, now: Duration) {
+		if let Some(media) = self.position_micros(now) {
+			self.anchor = Some(Anchor { wall: now, media });
+		}
+	}
+}
+
+impl Default for PlayoutClock {
+	fn default() -> Self {
+		Self::new(PlayoutConfig::default())
+	}
+}
+
+fn micros(duration: Duration) -> i64 {
+	duration.as_micros() as i64
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn ms(ms: u64) -> Duration {
+		Duration::from_millis(ms)
+	}
+
+	#[test]
+	fn wall() {
+		let mut clock = PlayoutClock::default();
+		assert_eq!(clock.position(ms(0)), None);
+
+		// The first frame is delayed by the latency target.
+		assert_eq!(clock.decide(ms(1000), ms(0)), Playout::Hold(Some(ms(100))));
+		assert_eq!(clock.decide(ms(1100), ms(0)), Playout::Render);
+		assert_eq!(clock.decide(ms(1100), ms(33)), Playout::Hold(Some(ms(33))));
+		assert_eq!(clock.source(ms(1100)), PlayoutSource::Wall);
+
+		// Frames more than 50ms late are dropped.
+		assert_eq!(clock.decide(ms(1200), ms(50)), Playout::Render);
+		assert_eq!(clock.decide(ms(1200), ms(49)), Playout::Drop);
+
+		// Lowering the latency target jumps ahead.
+		clock.set_latency(ms(1200), ms(50));
+		assert_eq!(clock.position(ms(1200)), Some(ms(150)));
+	}
+
+	#[test]
+	fn audio() {
+		let mut clock = PlayoutClock::default();
+
+		clock.update_audio(ms(1000), ms(500));
+		assert_eq!(clock.source(ms(1000)), PlayoutSource::Audio);
+		assert_eq!(clock.position(ms(1020)), Some(ms(520)));
+		assert_eq!(clock.decide(ms(1020), ms(520)), Playout::Render);
+		assert_eq!(clock.decide(ms(1020), ms(540)), Playout::Hold(Some(ms(20))));
+
+		// The audio position wins over the extrapolated position.
+		clock.update_audio(ms(1040), ms(530));
+		assert_eq!(clock.decide(ms(1040), ms(540)), Playout::Hold(Some(ms(10))));
+
+		// The wall clock keeps going when audio stops reporting.
+		assert_eq!(clock.source(ms(1300)), PlayoutSource::Wall);
+		assert_eq!(clock.position(ms(1300)), Some(ms(790)));
+	}
+
+	#[test]
+	fn rate() {
+		let mut clock = PlayoutClock::default();
+		clock.seek(ms(0), ms(0));
+
+		clock.set_rate(ms(100), 2.0).unwrap();
+		assert_eq!(clock.position(ms(200)), Some(ms(300)));
+		assert_eq!(clock.decide(ms(200), ms(400)), Playout::Hold(Some(ms(50))));
+
+		assert!(clock.set_rate(ms(200), 0.0).is_err());
+		assert!(clock.set_rate(ms(200), f64::NAN).is_err());
+	}
+
+	#[test]
+	fn pause() {
+		let mut clock = PlayoutClock::default();
+		clock.seek(ms(0), ms(0));
+
+		clock.pause(ms(100));
+		assert!(clock.is_paused());
+		assert_eq!(clock.position(ms(500)), Some(ms(100)));
+		assert_eq!(clock.decide(ms(500), ms(100)), Playout::Render);
+		assert_eq!(clock.decide(ms(500), ms(133)), Playout::Hold(None));
+
+		// Audio updates while paused are ignored.
+		clock.update_audio(ms(500), ms(1000));
+		assert_eq!(clock.position(ms(500)), Some(ms(100)));
+
+		clock.resume(ms(1000));
+		assert_eq!(clock.position(ms(1033)), Some(ms(133)));
+
+		// Seeking backwards drops the audio position until it's updated.
+		clock.update_audio(ms(1100), ms(200));
+		clock.seek(ms(1100), ms(10));
+		assert_eq!(clock.source(ms(1100)), PlayoutSource::Wall);
+		assert_eq!(clock.decide(ms(1100), ms(200)), Playout::Hold(Some(ms(190))));
+
+		// Reset re-anchors on the next frame.
+		clock.reset();
+		assert_eq!(clock.decide(ms(2000), ms(5000)), Playout::Hold(Some(ms(100))));
+	}
+}
@@ -0,0 +1,164 @@
+//! Bridges between a [web_sys::MediaStreamTrack] and the codecs, enabled with the `track` feature.
+//!
+//! Capture to encode:
+//! ```ignore
+//! let mut reader = TrackReader::<VideoFrame>::new(&camera)?;
+//! while let Some(frame) = reader.read().await? {
+//!     encoder.encode(&frame, Default::default())?;
+//! }
+//! ```
+//!
+//! Decode to display:
+//! ```ignore
+//! let mut writer = TrackWriter::<VideoFrame>::new()?;
+//! video.set_src_object(Some(&web_sys::MediaStream::new_with_tracks(&[writer.track()].iter().collect())?));
+//! while let Some(frame) = decoded.next().await? {
+//!     writer.write(frame).await?;
+//! }
+//! ```
+use wasm_bindgen::prelude::*;
+
+use crate::{AudioData, Error, Result, VideoFrame};
+
+/// A frame that can be read from or written to a [web_sys::MediaStreamTrack].
+pub trait TrackFrame: Sized {
+	/// The `kind` of the track, ex. `video`.
+	const KIND: &'static str;
+
+	#[doc(hidden)]
+	type Inner: JsCast;
+
+	#[doc(hidden)]
+	fn from_inner(inner: Self::Inner) -> Self;
+
+	/// Returns an object that the track generator is allowed to close.
+	#[doc(hidden)]
+	fn into_inner(self) -> Self::Inner;
+}
+
+impl TrackFrame for VideoFrame {
+	const KIND: &'static str = "video";
+	type Inner = web_sys::VideoFrame;
+
+	fn from_inner(inner: Self::Inner) -> Self {
+		inner.into()
+	}
+
+	fn into_inner(self) -> Self::Inner {
+		self.leak()
+	}
+}
+
+impl TrackFrame for AudioData {
+	const KIND: &'static str = "audio";
+	type Inner = web_sys::AudioData;
+
+	fn from_inner(inner: Self::Inner) -> Self {
+		inner.into()
+	}
+
+	fn into_inner(self) -> Self::Inner {
+		self.leak()
+	}
+}
+
+/// Reads frames from a [web_sys::MediaStreamTrack] using a [web_sys::MediaStreamTrackProcessor].
+///
+/// Each frame must be dropped promptly, otherwise the capture device may stall.
+pub struct TrackReader<T: TrackFrame> {
+	reader: web_streams::Reader<T::Inner>,
+}
+
+impl<T: TrackFrame> TrackReader<T> {
+	pub fn new(track: &web_sys::MediaStreamTrack) -> Result<Self> {
+		Self::with_init(track, web_sys::MediaStreamTrackProcessorInit::new(track))
+	}
+
+	/// Buffer up to `size` frames before the oldest are dropped, instead of the browser default.
+	pub fn with_max_buffer_size(track: &web_sys::MediaStreamTrack, size: u16) -> Result<Self> {
+		let init = web_sys::MediaStreamTrackProcessorInit::new(track);
+		init.set_max_buffer_size(size);
+
+		Self::with_init(track, init)
+	}
+
+	fn with_init(track: &web_sys::MediaStreamTrack, init: web_sys::MediaStreamTrackProcessorInit) -> Result<Self> {
+		if track.kind() != T::KIND {
+			return Err(Error::UnsupportedFormat);
+		}
+
+		let processor = web_sys::MediaStreamTrackProcessor::new(&init)?;
+		let reader = web_streams::Reader::new(&processor.readable())?;
+
+		Ok(Self { reader })
+	}
+
+	/// Returns the next frame, or None if the track has ended.
+	pub async fn read(&mut self) -> Result<Option<T>> {
+		Ok(self.reader.read().await?.map(T::from_inner))
+	}
+
+	/// Stop reading; the track itself is not stopped.
+	pub fn close(&mut self) {
+		self.reader.abort("closed");
+	}
+}
+
+/// Writes frames to a new [web_sys::MediaStreamTrack], ex. to display decoded video in a `<video>` element.
+pub struct TrackWriter<T: TrackFrame> {
+	writer: web_streams::TypedWriter<T::Inner>,
+	track: web_sys::MediaStreamTrack,
+}
+
+impl<T: TrackFrame> TrackWriter<T> {
+	/// Create a track, using `VideoTrackGenerator` when available and `MediaStreamTrackGenerator` otherwise.
+	pub fn new() -> Result<Self> {
+		if T::KIND == "video" {
+			// Currently only exposed in dedicated workers.
+			if let Ok(generator) = VideoTrackGenerator::new() {
+				return Self::with_stream(&generator.writable(), generator.track());
+			}
+		}
+
+		let init = web_sys::MediaStreamTrackGeneratorInit::new(T::KIND);
+		let generator = web_sys::MediaStreamTrackGenerator::new(&init)?;
+
+		Self::with_stream(&generator.writable(), generator.into())
+	}
+
+	fn with_stream(writable: &web_sys::WritableStream, track: web_sys::MediaStreamTrack) -> Result<Self> {
+		let writer = web_streams::TypedWriter::new(writable)?;
+		Ok(Self { writer, track })
+	}
+
+	/// The generated track, which can be added to a [web_sys::MediaStream].
+	pub fn track(&self) -> &web_sys::MediaStreamTrack {
+		&self.track
+	}
+
+	/// Write a frame, waiting until the track is ready for more.
+	pub async fn write(&mut self, frame: T) -> Result<()> {
+		self.writer.write(&frame.into_inner()).await?;
+		Ok(())
+	}
+
+	/// End the track.
+	pub fn close(&mut self) {
+		self.writer.close();
+	}
+}
+
+// Not in web-sys yet.
+#[wasm_bindgen]
+extern "C" {
+	type VideoTrackGenerator;
+
+	#[wasm_bindgen(constructor, catch)]
+	fn new() -> std::result::Result<VideoTrackGenerator, JsValue>;
+
+	#[wasm_bindgen(method, getter)]
+	fn writable(this: &VideoTrackGenerator) -> web_sys::WritableStream;
+
+	#[wasm_bindgen(method, getter)]
+	fn track(this: &VideoTrackGenerator) -> web_sys::MediaStreamTrack;
+}
@@ -0,0 +1,418 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::{EncodedFrame, Error, Result, Timestamp};
+
+use super::{Dimensions, VideoEncoderConfig};
+
+/// A resolution and framerate, used once the target bitrate is at least `min_bitrate`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AbrRung {
+	pub resolution: Dimensions,
+	pub framerate: Option<f64>,
+	pub min_bitrate: u32, // bits per second
+}
+
+#[derive(Debug, Clone)]
+pub struct AbrConfig {
+	pub min_bitrate: u32, // bits per second
+	pub max_bitrate: u32, // bits per second
+
+	/// The fraction of the estimated bandwidth to use, leaving headroom for audio and retransmissions.
+	pub utilization: f64,
+
+	/// Ignore bitrate changes smaller than this fraction, and require this much headroom before moving up a rung.
+	pub hysteresis: f64,
+
+	/// The minimum time between any two changes.
+	pub min_interval: Duration,
+
+	/// The minimum time after a decrease before increasing again.
+	pub increase_interval: Duration,
+
+	/// The encoder is overloaded if its queue is larger than this, and the next rung is used.
+	/// Without a ladder, the bitrate is lowered instead.
+	pub max_queue_size: u32,
+
+	/// Resolutions and framerates from highest to lowest quality.
+	/// If empty, only the bitrate is changed.
+	pub ladder: Vec<AbrRung>,
+}
+
+impl Default for AbrConfig {
+	fn default() -> Self {
+		Self {
+			min_bitrate: 100_000,
+			max_bitrate: 10_000_000,
+			utilization: 0.85,
+			hysteresis: 0.15,
+			min_interval: Duration::from_millis(500),
+			increase_interval: Duration::from_secs(2),
+			max_queue_size: 3,
+			ladder: Vec::new(),
+		}
+	}
+}
+
+/// Adapts a [crate::VideoEncoder] to the network, returning a new config for [crate::VideoEncoder::reconfigure].
+///
+/// The inputs are the external bandwidth estimate, the size of each [EncodedFrame] to detect encoder overshoot,
+/// and the encoder queue size to detect when it can't keep up. Decreases are applied quickly, while increases
+/// wait until the conditions have been stable for [AbrConfig::increase_interval].
+/// Like [crate::PlayoutClock], each method takes `now` from a monotonic wall clock.
+#[derive(Debug, Clone)]
+pub struct AbrController {
+	config: AbrConfig,
+
+	// The latest config, used as the base for the next one.
+	encoder: VideoEncoderConfig,
+	bitrate: u32,
+	rung: Option<usize>,
+
+	// The best rung allowed, lowered when the encoder is overloaded.
+	ceiling: usize,
+
+	bandwidth: Option<u64>,
+
+	// The timestamp and size of recent frames, used to measure the actual bitrate.
+	frames: VecDeque<(Timestamp, usize)>,
+
+	// The wall clock time of the last change and the last decrease.
+	changed: Option<Duration>,
+	decreased: Option<Duration>,
+}
+
+impl AbrController {
+	// The window used to measure the actual bitrate.
+	const WINDOW: Duration = Duration::from_secs(1);
+
+	// The fraction of the bitrate kept each time the encoder is overloaded, if there's no ladder.
+	const OVERLOAD_BACKOFF: f64 = 0.75;
+
+	/// Returns [Error::OutOfRange] if the bitrate range is empty or zero, or a fraction is negative.
+	pub fn new(config: AbrConfig, encoder: VideoEncoderConfig) -> Result<Self> {
+		if config.min_bitrate == 0
+			|| config.min_bitrate > config.max_bitrate
+			|| config.utilization.is_nan()
+			|| config.utilization <= 0.0
+			|| !(0.0..).contains(&config.hysteresis)
+		{
+			return Err(Error::OutOfRange);
+		}
+
+		let bitrate = encoder
+			.bitrate
+			.unwrap_or(config.max_bitrate)
+			.clamp(config.min_bitrate, config.max_bitrate);
+
+		let rung = config
+			.ladder
+			.iter()
+			.position(|rung| rung.resolution == encoder.resolution)
+			.or((!config.ladder.is_empty()).then_some(0));
+
+		// The bitrate is always at least min_bitrate, so it's never zero.
+		Ok(Self {
+			config,
+			encoder,
+			bitrate,
+			rung,
+			ceiling: 0,
+			bandwidth: None,
+			frames: VecDeque::new(),
+			changed: None,
+			decreased: None,
+		})
+	}
+
+	/// The latest config returned by [Self::update].
+	pub fn encoder_config(&self) -> &VideoEncoderConfig {
+		&self.encoder
+	}
+
+	/// Set the estimated bandwidth in bits per second, ex. from congestion control.
+	pub fn set_bandwidth(&mut self, bitrate: u64) {
+		self.bandwidth = Some(bitrate);
+	}
+
+	/// Record the size of an encoded frame.
+	pub fn on_frame(&mut self, frame: &EncodedFrame) {
+		self.frames.push_back((frame.timestamp, frame.payload.len()));
+
+		while let (Some(first), Some(last)) = (self.frames.front(), self.frames.back()) {
+			if last.0.saturating_sub(first.0) <= Self::WINDOW {
+				break;
+			}
+
+			self.frames.pop_front();
+		}
+	}
+
+	/// The actual bitrate of the recent frames, if there's enough to measure.
+	pub fn measured_bitrate(&self) -> Option<u64> {
+		let first = self.frames.front()?;
+		let last = self.frames.back()?;
+
+		// Assume the last frame lasts as long as the average frame.
+		let duration = last.0.saturating_sub(first.0).as_secs_f64() * self.frames.len() as f64
+			/ (self.frames.len() - 1).max(1) as f64;
+		if duration <= 0.0 {
+			return None;
+		}
+
+		let bytes: usize = self.frames.iter().map(|(_, size)| size).sum();
+		Some((bytes as f64 * 8.0 / duration) as u64)
+	}
+
+	/// Returns a new config if the encoder should be reconfigured.
+	pub fn update(&mut self, now: Duration, queue_size: u32) -> Option<VideoEncoderConfig> {
+		if self
+			.changed
+			.is_some_and(|changed| now.saturating_sub(changed) < self.config.min_interval)
+		{
+			return None;
+		}
+
+		let stable = self
+			.decreased
+			.is_none_or(|decreased| now.saturating_sub(decreased) >= self.config.increase_interval);
+
+		let overloaded = queue_size > self.config.max_queue_size;
+		if overloaded {
+			self.ceiling = (self.rung.unwrap_or(0) + 1).min(self.config.ladder.len().saturating_sub(1));
+		} else if stable && self.ceiling > 0 {
+			self.ceiling -= 1;
+		}
+
+		let mut bitrate = self.target_bitrate();
+		let rung = self.target_rung(bitrate, stable);
+
+		// Without a ladder, the only way to relieve the encoder is a lower bitrate.
+		let backoff = overloaded && self.config.ladder.is_empty();
+		if backoff {
+			let lower = (self.bitrate as f64 * Self::OVERLOAD_BACKOFF) as u32;
+			bitrate = bitrate.min(lower.max(self.config.min_bitrate));
+		}
+
+		let change = (bitrate as f64 - self.bitrate as f64) / self.bitrate as f64;
+		let bitrate = match change {
+			change if change < 0.0 && backoff => bitrate,
+			change if change <= -self.config.hysteresis => bitrate,
+			change if change >= self.config.hysteresis && stable => bitrate,
+			_ => self.bitrate,
+		};
+
+		if bitrate == self.bitrate && rung == self.rung {
+			return None;
+		}
+
+		if bitrate < self.bitrate || rung > self.rung {
+			self.decreased = Some(now);
+		}
+
+		self.bitrate = bitrate;
+		self.rung = rung;
+		self.changed = Some(now);
+
+		self.encoder.bitrate = Some(bitrate);
+		if let Some(rung) = rung.map(|rung| &self.config.ladder[rung]) {
+			self.encoder.resolution = rung.resolution;
+			self.encoder.framerate = rung.framerate.or(self.encoder.framerate);
+		}
+
+		Some(self.encoder.clone())
+	}
+
+	fn target_bitrate(&self) -> u32 {
+		let Some(bandwidth) = self.bandwidth else {
+			return self.bitrate;
+		};
+
+		let mut target = bandwidth as f64 * self.config.utilization;
+
+		// Compensate if the encoder is producing more than it was asked for.
+		if let Some(measured) = self.measured_bitrate() {
+			if measured > self.bitrate as u64 {
+				target *= self.bitrate as f64 / measured as f64;
+			}
+		}
+
+		(target as u32).clamp(self.config.min_bitrate, self.config.max_bitrate)
+	}
+
+	// The best rung for the bitrate, only moving up if there's enough headroom.
+	fn target_rung(&self, bitrate: u32, stable: bool) -> Option<usize> {
+		let current = self.rung?;
+
+		let best = self
+			.config
+			.ladder
+			.iter()
+			.position(|rung| bitrate >= rung.min_bitrate)
+			.unwrap_or(self.config.ladder.len() - 1)
+			.max(self.ceiling);
+
+		if best >= current {
+			return Some(best);
+		}
+
+		if !stable {
+			return Some(current);
+		}
+
+		// Move up one rung at a time, with headroom to avoid bouncing.
+		let next = current - 1;
+		let required = self.config.ladder[next].min_bitrate as f64 * (1.0 + self.config.hysteresis);
+		match bitrate as f64 >= required {
+			true => Some(next),
+			false => Some(current),
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use bytes::Bytes;
+
+	use super::*;
+
+	fn ms(ms: u64) -> Duration {
+		Duration::from_millis(ms)
+	}
+
+	fn ladder() -> AbrConfig {
+		AbrConfig {
+			ladder: vec![
+				AbrRung {
+					resolution: Dimensions::new(1280, 720),
+					framerate: Some(30.0),
+					min_bitrate: 1_500_000,
+				},
+				AbrRung {
+					resolution: Dimensions::new(640, 360),
+					framerate: Some(30.0),
+					min_bitrate: 500_000,
+				},
+				AbrRung {
+					resolution: Dimensions::new(320, 180),
+					framerate: Some(15.0),
+					min_bitrate: 0,
+				},
+			],
+			..Default::default()
+		}
+	}
+
+	fn encoder() -> VideoEncoderConfig {
+		let mut config = VideoEncoderConfig::new("vp8", Dimensions::new(1280, 720));
+		config.bitrate = Some(2_000_000);
+		config
+	}
+
+	#[test]
+	fn bandwidth() {
+		let mut abr = AbrController::new(AbrConfig::default(), encoder()).unwrap();
+		assert!(abr.update(ms(0), 0).is_none());
+
+		// Small changes are ignored.
+		abr.set_bandwidth(2_200_000);
+		assert!(abr.update(ms(0), 0).is_none());
+
+		// Decreases are immediate.
+		abr.set_bandwidth(1_000_000);
+		let config = abr.update(ms(0), 0).unwrap();
+		assert_eq!(config.bitrate, Some(850_000));
+		assert_eq!(config.resolution, Dimensions::new(1280, 720));
+
+		// Increases wait until it's been stable.
+		abr.set_bandwidth(3_000_000);
+		assert!(abr.update(ms(1000), 0).is_none());
+		assert_eq!(abr.update(ms(2000), 0).unwrap().bitrate, Some(2_550_000));
+	}
+
+	#[test]
+	fn ladder_hysteresis() {
+		let mut abr = AbrController::new(ladder(), encoder()).unwrap();
+
+		abr.set_bandwidth(1_000_000);
+		let config = abr.update(ms(0), 0).unwrap();
+		assert_eq!(config.bitrate, Some(850_000));
+		assert_eq!(config.resolution, Dimensions::new(640, 360));
+
+		// Just above the threshold isn't enough to move back up.
+		abr.set_bandwidth(2_000_000);
+		assert_eq!(abr.update(ms(5000), 0).unwrap().resolution, Dimensions::new(640, 360));
+
+		abr.set_bandwidth(2_500_000);
+		let config = abr.update(ms(10000), 0).unwrap();
+		assert_eq!(config.resolution, Dimensions::new(1280, 720));
+		assert_eq!(config.framerate, Some(30.0));
+	}
+
+	#[test]
+	fn overloaded() {
+		let mut abr = AbrController::new(ladder(), encoder()).unwrap();
+
+		// The encoder can't keep up, so drop a rung even though the bitrate is fine.
+		let config = abr.update(ms(0), 10).unwrap();
+		assert_eq!(config.resolution, Dimensions::new(640, 360));
+		assert_eq!(config.bitrate, Some(2_000_000));
+
+		assert_eq!(abr.update(ms(500), 10).unwrap().resolution, Dimensions::new(320, 180));
+		assert_eq!(abr.encoder_config().framerate, Some(15.0));
+
+		// Recover one rung at a time once the queue drains.
+		assert!(abr.update(ms(1000), 0).is_none());
+		assert_eq!(abr.update(ms(2500), 0).unwrap().resolution, Dimensions::new(640, 360));
+	}
+
+	#[test]
+	fn overloaded_without_ladder() {
+		let mut abr = AbrController::new(AbrConfig::default(), encoder()).unwrap();
+
+		// There's no rung to drop, so lower the bitrate instead.
+		assert_eq!(abr.update(ms(0), 10).unwrap().bitrate, Some(1_500_000));
+		assert_eq!(abr.update(ms(500), 10).unwrap().bitrate, Some(1_125_000));
+		assert_eq!(abr.encoder_config().resolution, Dimensions::new(1280, 720));
+	}
+
+	#[test]
+	fn invalid() {
+		let config = AbrConfig {
+			min_bitrate: 2_000_000,
+			max_bitrate: 1_000_000,
+			..Default::default()
+		};
+		assert!(matches!(AbrController::new(config, encoder()), Err(Error::OutOfRange)));
+
+		let config = AbrConfig {
+			min_bitrate: 0,
+			..Default::default()
+		};
+		assert!(matches!(AbrController::new(config, encoder()), Err(Error::OutOfRange)));
+	}
+
+	#[test]
+	fn overshoot() {
+		let mut abr = AbrController::new(AbrConfig::default(), encoder()).unwrap();
+
+		// The encoder is producing 4 Mb/s instead of 2 Mb/s.
+		for i in 0..30 {
+			abr.on_frame(&EncodedFrame {
+				payload: Bytes::from(vec![0; 16_666]).into(),
+				timestamp: Duration::from_micros(i * 33_333),
+				keyframe: i == 0,
+				temporal_layer: None,
+				alpha: None,
+				config_changed: false,
+			});
+		}
+
+		let measured = abr.measured_bitrate().unwrap();
+		assert!((3_900_000..4_100_000).contains(&measured), "{measured}");
+
+		// Half of the usual 85% of 3.5 Mb/s.
+		abr.set_bandwidth(3_500_000);
+		let bitrate = abr.update(ms(0), 0).unwrap().bitrate.unwrap();
+		assert!((1_450_000..1_550_000).contains(&bitrate), "{bitrate}");
+	}
+}
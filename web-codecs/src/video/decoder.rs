@@ -1,5 +1,8 @@
+use std::cell::{Cell, RefCell};
+
 use bytes::{Bytes, BytesMut};
 use tokio::sync::{mpsc, watch};
+#[cfg(not(mock))]
 use wasm_bindgen::prelude::*;
 
 use super::{Dimensions, VideoColorSpaceConfig, VideoFrame};
@@ -32,6 +35,11 @@ pub struct VideoDecoderConfig {
 
 	/// Optionally optimize for latency.
 	pub latency_optimized: Option<bool>,
+
+	// NOTE: This is a custom configuration
+	/// Recreate the decoder after an error instead of closing, skipping delta frames until the next keyframe.
+	/// Each recovery is reported as [VideoDecodedEvent::Recovered].
+	pub resilient: bool,
 }
 
 impl VideoDecoderConfig {
@@ -53,6 +61,16 @@ impl VideoDecoderConfig {
 			return Err(Error::InvalidDimensions);
 		}
 
+		self.is_supported_inner().await
+	}
+
+	#[cfg(mock)]
+	async fn is_supported_inner(&self) -> Result<bool, Error> {
+		Ok(true)
+	}
+
+	#[cfg(not(mock))]
+	async fn is_supported_inner(&self) -> Result<bool, Error> {
 		let res =
 			wasm_bindgen_futures::JsFuture::from(web_sys::VideoDecoder::is_config_supported(&self.into())).await?;
 
@@ -67,31 +85,8 @@ impl VideoDecoderConfig {
 	pub fn build(self) -> Result<(VideoDecoder, VideoDecoded), Error> {
 		let (frames_tx, frames_rx) = mpsc::unbounded_channel();
 		let (closed_tx, closed_rx) = watch::channel(Ok(()));
-		let closed_tx2 = closed_tx.clone();
-
-		let on_error = Closure::wrap(Box::new(move |e: JsValue| {
-			closed_tx.send_replace(Err(Error::from(e))).ok();
-		}) as Box<dyn FnMut(_)>);
-
-		let on_frame = Closure::wrap(Box::new(move |e: JsValue| {
-			let frame: web_sys::VideoFrame = e.unchecked_into();
-			let frame = VideoFrame::from(frame);
-
-			if frames_tx.send(frame).is_err() {
-				closed_tx2.send_replace(Err(Error::Dropped)).ok();
-			}
-		}) as Box<dyn FnMut(_)>);
-
-		let init = web_sys::VideoDecoderInit::new(on_error.as_ref().unchecked_ref(), on_frame.as_ref().unchecked_ref());
-		let inner: web_sys::VideoDecoder = web_sys::VideoDecoder::new(&init).unwrap();
-		inner.configure(&(&self).into())?;
-
-		let decoder = VideoDecoder {
-			inner,
-			on_error,
-			on_frame,
-		};
 
+		let decoder = VideoDecoder::new(&self, frames_tx, closed_tx)?;
 		let decoded = VideoDecoded {
 			frames: frames_rx,
 			closed: closed_rx,
@@ -180,65 +175,247 @@ impl From<web_sys::VideoDecoderConfig> for VideoDecoderConfig {
 			description,
 			hardware_acceleration,
 			latency_optimized,
+			resilient: false,
 		}
 	}
 }
 
 pub struct VideoDecoder {
+	codec: RefCell<VideoDecoderCodec>,
+
+	// Only set in resilient mode.
+	resilient: Option<Resilient>,
+}
+
+// Everything needed to recreate the codec after an error.
+struct Resilient {
+	config: VideoDecoderConfig,
+	frames: mpsc::UnboundedSender<VideoDecodedEvent>,
+	closed: watch::Sender<Result<(), Error>>,
+
+	// Errors from the current codec, which are recovered instead of closing.
+	errors: RefCell<watch::Receiver<Result<(), Error>>>,
+	keyframe_required: Cell<bool>,
+}
+
+impl VideoDecoder {
+	fn new(
+		config: &VideoDecoderConfig,
+		frames: mpsc::UnboundedSender<VideoDecodedEvent>,
+		closed: watch::Sender<Result<(), Error>>,
+	) -> Result<Self, Error> {
+		if !config.resilient {
+			let codec = VideoDecoderCodec::new(config, frames, closed)?;
+			return Ok(Self {
+				codec: RefCell::new(codec),
+				resilient: None,
+			});
+		}
+
+		let (errors_tx, errors_rx) = watch::channel(Ok(()));
+		let codec = VideoDecoderCodec::new(config, frames.clone(), errors_tx)?;
+
+		Ok(Self {
+			codec: RefCell::new(codec),
+			resilient: Some(Resilient {
+				config: config.clone(),
+				frames,
+				closed,
+				errors: RefCell::new(errors_rx),
+				keyframe_required: Cell::new(false),
+			}),
+		})
+	}
+
+	pub fn decode(&self, frame: EncodedFrame) -> Result<(), Error> {
+		if let Some(resilient) = &self.resilient {
+			if !self.recover(resilient, &frame)? {
+				return Ok(());
+			}
+		}
+
+		self.codec.borrow().decode(frame)
+	}
+
+	// Recreate the codec if it errored, returning false if the frame should be skipped.
+	fn recover(&self, resilient: &Resilient, frame: &EncodedFrame) -> Result<bool, Error> {
+		let err = resilient.errors.borrow().borrow().clone().err();
+
+		if let Some(err) = err {
+			// The output was dropped, so there's nothing to recover.
+			if let Error::Dropped = err {
+				resilient.closed.send_replace(Err(Error::Dropped)).ok();
+				return Err(Error::Dropped);
+			}
+
+			let (errors_tx, errors_rx) = watch::channel(Ok(()));
+			*self.codec.borrow_mut() = VideoDecoderCodec::new(&resilient.config, resilient.frames.clone(), errors_tx)?;
+			*resilient.errors.borrow_mut() = errors_rx;
+
+			resilient.keyframe_required.set(true);
+			resilient.frames.send(VideoDecodedEvent::Recovered(err)).ok();
+		}
+
+		if resilient.keyframe_required.get() {
+			if !frame.keyframe {
+				return Ok(false);
+			}
+
+			resilient.keyframe_required.set(false);
+		}
+
+		Ok(true)
+	}
+
+	pub async fn flush(&self) -> Result<(), Error> {
+		let flush = self.codec.borrow().flush();
+		flush.await
+	}
+
+	pub fn queue_size(&self) -> u32 {
+		self.codec.borrow().queue_size()
+	}
+}
+
+// A single instance of the underlying decoder.
+struct VideoDecoderCodec {
+	#[cfg(not(mock))]
 	inner: web_sys::VideoDecoder,
 
+	#[cfg(mock)]
+	inner: crate::mock::VideoDecoder,
+
 	// These are held to avoid dropping them.
+	#[cfg(not(mock))]
 	#[allow(dead_code)]
 	on_error: Closure<dyn FnMut(JsValue)>,
+	#[cfg(not(mock))]
 	#[allow(dead_code)]
 	on_frame: Closure<dyn FnMut(JsValue)>,
 }
 
-impl VideoDecoder {
-	pub fn decode(&self, frame: EncodedFrame) -> Result<(), Error> {
+#[cfg(not(mock))]
+impl VideoDecoderCodec {
+	fn new(
+		config: &VideoDecoderConfig,
+		frames: mpsc::UnboundedSender<VideoDecodedEvent>,
+		closed: watch::Sender<Result<(), Error>>,
+	) -> Result<Self, Error> {
+		let closed2 = closed.clone();
+
+		let on_error = Closure::wrap(Box::new(move |e: JsValue| {
+			closed.send_replace(Err(Error::from(e))).ok();
+		}) as Box<dyn FnMut(_)>);
+
+		let on_frame = Closure::wrap(Box::new(move |e: JsValue| {
+			let frame: web_sys::VideoFrame = e.unchecked_into();
+			let frame = VideoFrame::from(frame);
+
+			if frames.send(VideoDecodedEvent::Frame(frame)).is_err() {
+				closed2.send_replace(Err(Error::Dropped)).ok();
+			}
+		}) as Box<dyn FnMut(_)>);
+
+		let init = web_sys::VideoDecoderInit::new(on_error.as_ref().unchecked_ref(), on_frame.as_ref().unchecked_ref());
+		let inner: web_sys::VideoDecoder = web_sys::VideoDecoder::new(&init).unwrap();
+		inner.configure(&config.into())?;
+
+		Ok(Self {
+			inner,
+			on_error,
+			on_frame,
+		})
+	}
+
+	fn decode(&self, frame: EncodedFrame) -> Result<(), Error> {
 		let chunk_type = match frame.keyframe {
 			true => web_sys::EncodedVideoChunkType::Key,
 			false => web_sys::EncodedVideoChunkType::Delta,
 		};
 
-		let chunk = web_sys::EncodedVideoChunkInit::new(
-			&js_sys::Uint8Array::from(frame.payload.as_ref()),
-			frame.timestamp.as_micros() as _,
-			chunk_type,
-		);
-
-		let chunk = web_sys::EncodedVideoChunk::new(&chunk)?;
+		let chunk = frame.payload.chunk(frame.timestamp, chunk_type)?;
 		self.inner.decode(&chunk)?;
 
 		Ok(())
 	}
 
-	pub async fn flush(&self) -> Result<(), Error> {
-		wasm_bindgen_futures::JsFuture::from(self.inner.flush()).await?;
-		Ok(())
+	// Returns a future so the codec isn't borrowed while waiting.
+	fn flush(&self) -> impl std::future::Future<Output = Result<(), Error>> {
+		let promise = wasm_bindgen_futures::JsFuture::from(self.inner.flush());
+
+		async move {
+			promise.await?;
+			Ok(())
+		}
 	}
 
-	pub fn queue_size(&self) -> u32 {
+	fn queue_size(&self) -> u32 {
 		self.inner.decode_queue_size()
 	}
 }
 
-impl Drop for VideoDecoder {
+#[cfg(mock)]
+impl VideoDecoderCodec {
+	fn new(
+		_config: &VideoDecoderConfig,
+		frames: mpsc::UnboundedSender<VideoDecodedEvent>,
+		closed: watch::Sender<Result<(), Error>>,
+	) -> Result<Self, Error> {
+		let inner = crate::mock::VideoDecoder::new(frames, closed);
+		Ok(Self { inner })
+	}
+
+	fn decode(&self, frame: EncodedFrame) -> Result<(), Error> {
+		self.inner.decode(frame)
+	}
+
+	fn flush(&self) -> impl std::future::Future<Output = Result<(), Error>> {
+		self.inner.flush();
+		std::future::ready(Ok(()))
+	}
+
+	fn queue_size(&self) -> u32 {
+		0
+	}
+}
+
+impl Drop for VideoDecoderCodec {
 	fn drop(&mut self) {
 		let _ = self.inner.close();
 	}
 }
 
+/// An output of [VideoDecoded::next_event].
+#[derive(Debug)]
+pub enum VideoDecodedEvent {
+	Frame(VideoFrame),
+
+	/// The decoder failed with the given error and was recreated, only emitted in resilient mode.
+	/// Frames are skipped until the next keyframe.
+	Recovered(Error),
+}
+
 pub struct VideoDecoded {
-	frames: mpsc::UnboundedReceiver<VideoFrame>,
+	frames: mpsc::UnboundedReceiver<VideoDecodedEvent>,
 	closed: watch::Receiver<Result<(), Error>>,
 }
 
 impl VideoDecoded {
+	/// Returns the next frame, ignoring any recovery events.
 	pub async fn next(&mut self) -> Result<Option<VideoFrame>, Error> {
+		loop {
+			match self.next_event().await? {
+				Some(VideoDecodedEvent::Frame(frame)) => return Ok(Some(frame)),
+				Some(VideoDecodedEvent::Recovered(_)) => continue,
+				None => return Ok(None),
+			}
+		}
+	}
+
+	pub async fn next_event(&mut self) -> Result<Option<VideoDecodedEvent>, Error> {
 		tokio::select! {
 			biased;
-			frame = self.frames.recv() => Ok(frame),
+			event = self.frames.recv() => Ok(event),
 			Ok(()) = self.closed.changed() => Err(self.closed.borrow().clone().err().unwrap()),
 		}
 	}
@@ -1,11 +1,16 @@
-use std::{cell::RefCell, rc::Rc, time::Duration};
+use std::{
+	cell::{Cell, RefCell},
+	rc::Rc,
+	time::Duration,
+};
 
 use tokio::sync::{mpsc, watch};
+#[cfg(not(mock))]
 use wasm_bindgen::prelude::*;
 
 use crate::{EncodedFrame, Error, Timestamp};
 
-use super::{Dimensions, VideoDecoderConfig, VideoFrame};
+use super::{Dimensions, ScalabilityMode, VideoDecoderConfig, VideoFrame};
 
 use derive_more::Display;
 
@@ -31,7 +36,7 @@ pub struct VideoEncoderConfig {
 	pub bitrate: Option<u32>,          // bits per second
 	pub framerate: Option<f64>,        // frames per second
 	pub alpha_preserved: Option<bool>, // keep alpha channel
-	pub scalability_mode: Option<String>,
+	pub scalability_mode: Option<ScalabilityMode>,
 	pub bitrate_mode: Option<VideoBitrateMode>,
 
 	// NOTE: This is a custom configuration
@@ -56,6 +61,13 @@ impl VideoEncoderConfig {
 		}
 	}
 
+	#[cfg(mock)]
+	pub async fn is_supported(&self) -> Result<bool, Error> {
+		self.is_valid()?;
+		Ok(true)
+	}
+
+	#[cfg(not(mock))]
 	pub async fn is_supported(&self) -> Result<bool, Error> {
 		let res =
 			wasm_bindgen_futures::JsFuture::from(web_sys::VideoEncoder::is_config_supported(&self.into())).await?;
@@ -87,7 +99,14 @@ impl VideoEncoderConfig {
 		let (closed_tx, closed_rx) = watch::channel(Ok(()));
 		let config = Rc::new(RefCell::new(None));
 
-		let decoder = VideoEncoder::new(self, config.clone(), frames_tx, closed_tx)?;
+		let output = VideoEncoderOutput {
+			config: config.clone(),
+			last_keyframe: Default::default(),
+			frames: frames_tx,
+			closed: closed_tx,
+		};
+
+		let decoder = VideoEncoder::new(self, output)?;
 		let decoded = VideoEncoded::new(config, frames_rx, closed_rx);
 
 		Ok((decoder, decoded))
@@ -133,7 +152,7 @@ impl From<&VideoEncoderConfig> for web_sys::VideoEncoderConfig {
 		}
 
 		if let Some(value) = &this.scalability_mode {
-			config.set_scalability_mode(value);
+			config.set_scalability_mode(value.as_str());
 		}
 
 		if let Some(_value) = &this.bitrate_mode {
@@ -152,60 +171,106 @@ pub struct VideoEncodeOptions {
 	// pub quantizer: Option<u8>,
 }
 
+/// The shared state updated whenever the encoder outputs a frame.
+#[derive(Clone)]
+pub(crate) struct VideoEncoderOutput {
+	config: Rc<RefCell<Option<VideoDecoderConfig>>>,
+	last_keyframe: Rc<RefCell<Option<Timestamp>>>,
+	frames: mpsc::UnboundedSender<EncodedFrame>,
+	closed: watch::Sender<Result<(), Error>>,
+}
+
+impl VideoEncoderOutput {
+	pub fn frame(&self, mut frame: EncodedFrame, config: Option<VideoDecoderConfig>) {
+		frame.config_changed = config.is_some();
+
+		if let Some(config) = config {
+			self.config.borrow_mut().replace(config);
+		}
+
+		if frame.keyframe {
+			let mut last_keyframe = self.last_keyframe.borrow_mut();
+			if frame.timestamp > last_keyframe.unwrap_or_default() {
+				*last_keyframe = Some(frame.timestamp);
+			}
+		}
+
+		if self.frames.send(frame).is_err() {
+			self.closed.send_replace(Err(Error::Dropped)).ok();
+		}
+	}
+
+	#[cfg(not(mock))]
+	pub fn error(&self, err: Error) {
+		self.closed.send_replace(Err(err)).ok();
+	}
+}
+
 pub struct VideoEncoder {
+	#[cfg(not(mock))]
 	inner: web_sys::VideoEncoder,
+
+	#[cfg(mock)]
+	inner: crate::mock::VideoEncoder,
+
 	config: VideoEncoderConfig,
 
 	last_keyframe: Rc<RefCell<Option<Timestamp>>>,
 
+	// Set after changing the resolution, since the next frame can't reference the old ones.
+	force_keyframe: Cell<bool>,
+
 	// These are held to avoid dropping them.
+	#[cfg(not(mock))]
 	#[allow(dead_code)]
 	on_error: Closure<dyn FnMut(JsValue)>,
+	#[cfg(not(mock))]
 	#[allow(dead_code)]
 	on_frame: Closure<dyn FnMut(JsValue, JsValue)>,
 }
 
+#[cfg(not(mock))]
 impl VideoEncoder {
-	fn new(
-		config: VideoEncoderConfig,
-		on_config: Rc<RefCell<Option<VideoDecoderConfig>>>,
-		on_frame: mpsc::UnboundedSender<EncodedFrame>,
-		on_error: watch::Sender<Result<(), Error>>,
-	) -> Result<Self, Error> {
-		let last_keyframe = Rc::new(RefCell::new(None));
-		let last_keyframe2 = last_keyframe.clone();
-
-		let on_error2 = on_error.clone();
+	fn new(config: VideoEncoderConfig, output: VideoEncoderOutput) -> Result<Self, Error> {
+		let last_keyframe = output.last_keyframe.clone();
+
+		let output2 = output.clone();
 		let on_error = Closure::wrap(Box::new(move |e: JsValue| {
-			on_error.send_replace(Err(Error::from(e))).ok();
+			output2.error(Error::from(e));
 		}) as Box<dyn FnMut(_)>);
 
 		let on_frame = Closure::wrap(Box::new(move |frame: JsValue, meta: JsValue| {
 			// First parameter is the frame, second optional parameter is metadata.
 			let frame: web_sys::EncodedVideoChunk = frame.unchecked_into();
-			let frame = EncodedFrame::from(frame);
+			let mut frame = EncodedFrame::from(frame);
+
+			let mut decoder_config = None;
 
 			if let Ok(metadata) = meta.dyn_into::<js_sys::Object>() {
-				// TODO handle metadata
+				if let Ok(alpha) = js_sys::Reflect::get(&metadata, &"alphaSideData".into()) {
+					if !alpha.is_falsy() {
+						frame.alpha = Some(js_sys::Uint8Array::new(&alpha).into());
+					}
+				}
+
+				if let Ok(svc) = js_sys::Reflect::get(&metadata, &"svc".into()) {
+					if !svc.is_falsy() {
+						frame.temporal_layer = js_sys::Reflect::get(&svc, &"temporalLayerId".into())
+							.ok()
+							.and_then(|id| id.as_f64())
+							.map(|id| id as u8);
+					}
+				}
+
 				if let Ok(config) = js_sys::Reflect::get(&metadata, &"decoderConfig".into()) {
 					if !config.is_falsy() {
 						let config: web_sys::VideoDecoderConfig = config.unchecked_into();
-						let config = VideoDecoderConfig::from(config);
-						on_config.borrow_mut().replace(config);
+						decoder_config = Some(VideoDecoderConfig::from(config));
 					}
 				}
 			}
 
-			if frame.keyframe {
-				let mut last_keyframe = last_keyframe2.borrow_mut();
-				if frame.timestamp > last_keyframe.unwrap_or_default() {
-					*last_keyframe = Some(frame.timestamp);
-				}
-			}
-
-			if on_frame.send(frame).is_err() {
-				on_error2.send_replace(Err(Error::Dropped)).ok();
-			}
+			output.frame(frame, decoder_config);
 		}) as Box<dyn FnMut(_, _)>);
 
 		let init = web_sys::VideoEncoderInit::new(on_error.as_ref().unchecked_ref(), on_frame.as_ref().unchecked_ref());
@@ -216,26 +281,28 @@ impl VideoEncoder {
 			config,
 			inner,
 			last_keyframe,
+			force_keyframe: Cell::new(false),
 			on_error,
 			on_frame,
 		})
 	}
 
+	/// Change the configuration, ex. the bitrate, without closing the encoder.
+	///
+	/// The output channel is kept, and the decoder config is updated when the encoder emits a new one.
+	pub fn reconfigure(&mut self, config: VideoEncoderConfig) -> Result<(), Error> {
+		config.is_valid()?;
+		self.inner.configure(&(&config).into())?;
+		self.configured(config);
+
+		Ok(())
+	}
+
 	pub fn encode(&mut self, frame: &VideoFrame, options: VideoEncodeOptions) -> Result<(), Error> {
 		let o = web_sys::VideoEncoderEncodeOptions::new();
 
-		if let Some(key_frame) = options.key_frame {
+		if let Some(key_frame) = self.key_frame(frame, options) {
 			o.set_key_frame(key_frame);
-		} else if let Some(max_gop_duration) = self.config.max_gop_duration {
-			let timestamp = frame.timestamp();
-			let mut last_keyframe = self.last_keyframe.borrow_mut();
-
-			let duration = timestamp - last_keyframe.unwrap_or_default();
-			if duration >= max_gop_duration {
-				o.set_key_frame(true);
-			}
-
-			*last_keyframe = Some(timestamp);
 		}
 
 		self.inner.encode_with_options(frame, &o)?;
@@ -247,16 +314,95 @@ impl VideoEncoder {
 		self.inner.encode_queue_size()
 	}
 
-	pub fn config(&self) -> &VideoEncoderConfig {
-		&self.config
+	pub async fn flush(&mut self) -> Result<(), Error> {
+		wasm_bindgen_futures::JsFuture::from(self.inner.flush()).await?;
+		Ok(())
+	}
+}
+
+#[cfg(mock)]
+impl VideoEncoder {
+	fn new(config: VideoEncoderConfig, output: VideoEncoderOutput) -> Result<Self, Error> {
+		config.is_valid()?;
+
+		let last_keyframe = output.last_keyframe.clone();
+		let inner = crate::mock::VideoEncoder::new(&config, output);
+
+		Ok(Self {
+			config,
+			inner,
+			last_keyframe,
+			force_keyframe: Cell::new(false),
+		})
+	}
+
+	/// Change the configuration, ex. the bitrate, without closing the encoder.
+	///
+	/// The output channel is kept, and the decoder config is updated when the encoder emits a new one.
+	pub fn reconfigure(&mut self, config: VideoEncoderConfig) -> Result<(), Error> {
+		config.is_valid()?;
+		self.inner.configure(&config);
+		self.configured(config);
+
+		Ok(())
+	}
+
+	pub fn encode(&mut self, frame: &VideoFrame, options: VideoEncodeOptions) -> Result<(), Error> {
+		let key_frame = self.key_frame(frame, options).unwrap_or_default();
+		self.inner.encode(frame, key_frame);
+
+		Ok(())
+	}
+
+	pub fn queue_size(&self) -> u32 {
+		0
 	}
 
 	pub async fn flush(&mut self) -> Result<(), Error> {
-		wasm_bindgen_futures::JsFuture::from(self.inner.flush()).await?;
 		Ok(())
 	}
 }
 
+impl VideoEncoder {
+	// Returns if a keyframe should be forced or denied, otherwise it's up to the encoder.
+	fn key_frame(&self, frame: &VideoFrame, options: VideoEncodeOptions) -> Option<bool> {
+		let timestamp = frame.timestamp();
+		let mut last_keyframe = self.last_keyframe.borrow_mut();
+
+		// Takes priority over the caller, since a delta frame can't follow a resolution change.
+		if self.force_keyframe.take() {
+			*last_keyframe = Some(timestamp);
+			return Some(true);
+		}
+
+		if options.key_frame.is_some() {
+			return options.key_frame;
+		}
+
+		let max_gop_duration = self.config.max_gop_duration?;
+
+		let duration = timestamp.saturating_sub(last_keyframe.unwrap_or_default());
+		if duration < max_gop_duration {
+			return None;
+		}
+
+		*last_keyframe = Some(timestamp);
+		Some(true)
+	}
+
+	pub fn config(&self) -> &VideoEncoderConfig {
+		&self.config
+	}
+
+	fn configured(&mut self, config: VideoEncoderConfig) {
+		if config.resolution != self.config.resolution || config.codec != self.config.codec {
+			self.force_keyframe.set(true);
+		}
+
+		self.config = config;
+	}
+}
+
 impl Drop for VideoEncoder {
 	fn drop(&mut self) {
 		let _ = self.inner.close();
@@ -3,62 +3,174 @@ use std::{
 	time::Duration,
 };
 
-use derive_more::From;
-
-use crate::Timestamp;
+use crate::{Error, Result, Timestamp};
 
 use super::Dimensions;
 
-#[derive(Debug, From)]
-pub struct VideoFrame(web_sys::VideoFrame);
+pub type VideoPixelFormat = web_sys::VideoPixelFormat;
+
+/// The number of bytes needed to store a tightly packed frame.
+///
+/// Returns None if the format is unsupported or the size overflows a usize.
+pub(crate) fn allocation_size(format: VideoPixelFormat, dimensions: Dimensions) -> Option<usize> {
+	let width = dimensions.width as usize;
+	let height = dimensions.height as usize;
+
+	let luma = width.checked_mul(height)?;
+	let chroma = width.div_ceil(2).checked_mul(height.div_ceil(2))?;
+
+	match format {
+		VideoPixelFormat::I420 | VideoPixelFormat::Nv12 => luma.checked_add(chroma.checked_mul(2)?),
+		VideoPixelFormat::I420a => luma.checked_add(chroma)?.checked_mul(2),
+		VideoPixelFormat::I422 => luma.checked_add(width.div_ceil(2).checked_mul(height)?.checked_mul(2)?),
+		VideoPixelFormat::I444 => luma.checked_mul(3),
+		VideoPixelFormat::Rgba | VideoPixelFormat::Rgbx | VideoPixelFormat::Bgra | VideoPixelFormat::Bgrx => {
+			luma.checked_mul(4)
+		}
+		_ => None,
+	}
+}
+
+#[cfg(not(mock))]
+type VideoFrameInner = web_sys::VideoFrame;
+
+#[cfg(mock)]
+type VideoFrameInner = crate::mock::VideoFrame;
+
+/// A wrapper around [web_sys::VideoFrame] that closes on Drop.
+// It's an option so `leak` can return the inner VideoFrame if needed.
+#[derive(Debug)]
+pub struct VideoFrame(Option<VideoFrameInner>);
 
 impl VideoFrame {
 	pub fn timestamp(&self) -> Timestamp {
-		Timestamp::from_micros(self.0.timestamp().unwrap() as _)
+		Timestamp::from_micros(self.0.as_ref().unwrap().timestamp().unwrap() as _)
 	}
 
 	pub fn duration(&self) -> Option<Duration> {
-		Some(Duration::from_micros(self.0.duration()? as _))
+		Some(Duration::from_micros(self.0.as_ref().unwrap().duration()? as _))
 	}
 
 	pub fn dimensions(&self) -> Dimensions {
 		Dimensions {
-			width: self.0.coded_width(),
-			height: self.0.coded_height(),
+			width: self.0.as_ref().unwrap().coded_width(),
+			height: self.0.as_ref().unwrap().coded_height(),
 		}
 	}
+
+	pub fn format(&self) -> Option<VideoPixelFormat> {
+		self.0.as_ref().unwrap().format()
+	}
+}
+
+#[cfg(not(mock))]
+impl VideoFrame {
+	/// Create a frame from tightly packed pixel data, ex. planar I420 or interleaved RGBA.
+	pub fn new(data: &[u8], format: VideoPixelFormat, dimensions: Dimensions, timestamp: Timestamp) -> Result<Self> {
+		if dimensions.width == 0 || dimensions.height == 0 {
+			return Err(Error::InvalidDimensions);
+		}
+
+		let init =
+			web_sys::VideoFrameBufferInit::new(dimensions.height, dimensions.width, format, timestamp.as_micros() as _);
+
+		let data = js_sys::Uint8Array::from(data);
+		let frame = web_sys::VideoFrame::new_with_u8_array_and_video_frame_buffer_init(&data, &init)?;
+		Ok(Self(Some(frame)))
+	}
+
+	/// The number of bytes required by [Self::copy_to].
+	pub fn allocation_size(&self) -> Result<usize> {
+		Ok(self.0.as_ref().unwrap().allocation_size()? as usize)
+	}
+
+	/// Copy the tightly packed pixel data into the given buffer.
+	pub async fn copy_to(&self, dst: &mut [u8]) -> Result<()> {
+		let size = self.allocation_size()?;
+		let dst = dst.get_mut(..size).ok_or(Error::OutOfRange)?;
+
+		// The copy is asynchronous, so we can't let wasm-bindgen borrow the slice.
+		let buffer = js_sys::Uint8Array::new_with_length(size as _);
+		wasm_bindgen_futures::JsFuture::from(self.0.as_ref().unwrap().copy_to_with_u8_array(&buffer)).await?;
+		buffer.copy_to(dst);
+
+		Ok(())
+	}
+
+	/// Return the inner frame without closing it, transferring ownership to the caller.
+	pub fn leak(mut self) -> web_sys::VideoFrame {
+		self.0.take().unwrap()
+	}
+}
+
+#[cfg(mock)]
+impl VideoFrame {
+	/// Create a frame from tightly packed pixel data, ex. planar I420 or interleaved RGBA.
+	pub fn new(data: &[u8], format: VideoPixelFormat, dimensions: Dimensions, timestamp: Timestamp) -> Result<Self> {
+		let frame = crate::mock::VideoFrame::new(data, format, dimensions, timestamp)?;
+		Ok(Self(Some(frame)))
+	}
+
+	/// The number of bytes required by [Self::copy_to].
+	pub fn allocation_size(&self) -> Result<usize> {
+		Ok(self.0.as_ref().unwrap().allocation_size())
+	}
+
+	/// Copy the tightly packed pixel data into the given buffer.
+	pub async fn copy_to(&self, dst: &mut [u8]) -> Result<()> {
+		let src = self.0.as_ref().unwrap().as_bytes();
+		let dst = dst.get_mut(..src.len()).ok_or(Error::OutOfRange)?;
+		dst.copy_from_slice(src);
+
+		Ok(())
+	}
 }
 
 // Avoid closing the video frame on transfer by cloning it.
+#[cfg(not(mock))]
 impl From<VideoFrame> for web_sys::VideoFrame {
 	fn from(this: VideoFrame) -> Self {
-		this.0.clone().expect("detached")
+		this.0.as_ref().unwrap().clone().expect("detached")
 	}
 }
 
 impl Clone for VideoFrame {
+	#[cfg(not(mock))]
+	fn clone(&self) -> Self {
+		Self(Some(self.0.as_ref().unwrap().clone().expect("detached")))
+	}
+
+	#[cfg(mock)]
 	fn clone(&self) -> Self {
-		Self(self.0.clone().expect("detached"))
+		Self(self.0.clone())
 	}
 }
 
 impl Deref for VideoFrame {
-	type Target = web_sys::VideoFrame;
+	type Target = VideoFrameInner;
 
 	fn deref(&self) -> &Self::Target {
-		&self.0
+		self.0.as_ref().unwrap()
 	}
 }
 
 impl DerefMut for VideoFrame {
 	fn deref_mut(&mut self) -> &mut Self::Target {
-		&mut self.0
+		self.0.as_mut().unwrap()
 	}
 }
 
 // Make sure we close the frame on drop.
 impl Drop for VideoFrame {
 	fn drop(&mut self) {
-		self.0.close();
+		if let Some(frame) = self.0.take() {
+			frame.close();
+		}
+	}
+}
+
+impl From<VideoFrameInner> for VideoFrame {
+	fn from(this: VideoFrameInner) -> Self {
+		Self(Some(this))
 	}
 }
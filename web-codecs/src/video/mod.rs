@@ -1,11 +1,19 @@
+mod abr;
 mod color;
 mod decoder;
 mod dimensions;
 mod encoder;
 mod frame;
+mod simulcast;
+mod svc;
+mod y4m;
 
+pub use abr::*;
 pub use color::*;
 pub use decoder::*;
 pub use dimensions::*;
 pub use encoder::*;
 pub use frame::*;
+pub use simulcast::*;
+pub use svc::*;
+pub use y4m::*;
@@ -0,0 +1,406 @@
+use std::{future::Future, pin::Pin, task::Poll, time::Duration};
+
+use crate::{EncodedFrame, Error, Timestamp};
+
+use super::{
+	Dimensions, VideoDecoderConfig, VideoEncodeOptions, VideoEncoded, VideoEncoder, VideoEncoderConfig, VideoFrame,
+};
+
+// The target bits per pixel used to compute a ladder, roughly 2.8 Mb/s for 720p30.
+const BITS_PER_PIXEL: f64 = 0.1;
+
+// The smallest height used when computing a ladder.
+const MIN_HEIGHT: u32 = 90;
+
+#[derive(Debug, Clone)]
+pub struct SimulcastRendition {
+	/// The encoder config; any `max_gop_duration` is replaced by the one in [SimulcastConfig].
+	pub config: VideoEncoderConfig,
+
+	/// Only encode every Nth frame, ex. 2 for half the frame rate.
+	pub decimation: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SimulcastConfig {
+	/// The renditions, identified by their index.
+	pub renditions: Vec<SimulcastRendition>,
+
+	/// The maximum duration of a Group of Pictures (GOP), shared by all renditions.
+	pub max_gop_duration: Option<Duration>,
+}
+
+impl SimulcastConfig {
+	/// Compute a ladder of up to `count` renditions, each half the resolution of the previous one.
+	///
+	/// The bitrate is proportional to the number of pixels per second.
+	/// The smallest rendition uses half the frame rate when there are at least three.
+	pub fn ladder<T: Into<String>>(codec: T, source: Dimensions, framerate: f64, count: usize) -> Self {
+		let codec = codec.into();
+		let mut renditions = Vec::new();
+		let mut resolution = source;
+
+		while renditions.len() < count {
+			let mut config = VideoEncoderConfig::new(codec.clone(), resolution);
+			config.framerate = Some(framerate);
+			config.bitrate =
+				Some((resolution.width as f64 * resolution.height as f64 * framerate * BITS_PER_PIXEL) as u32);
+
+			renditions.push(SimulcastRendition { config, decimation: 1 });
+
+			// Codecs generally require even dimensions because of chroma subsampling.
+			resolution = Dimensions::new(resolution.width / 4 * 2, resolution.height / 4 * 2);
+			if resolution.height < MIN_HEIGHT || resolution.width == 0 {
+				break;
+			}
+		}
+
+		if renditions.len() >= 3 {
+			let smallest = renditions.last_mut().unwrap();
+			smallest.decimation = 2;
+			smallest.config.framerate = Some(framerate / 2.0);
+			smallest.config.bitrate = smallest.config.bitrate.map(|bitrate| bitrate / 2);
+		}
+
+		Self {
+			renditions,
+			max_gop_duration: Some(Duration::from_secs(2)),
+		}
+	}
+
+	pub fn init(self) -> Result<(SimulcastEncoder, SimulcastEncoded), Error> {
+		let mut encoders = Vec::with_capacity(self.renditions.len());
+		let mut encoded = Vec::with_capacity(self.renditions.len());
+
+		for rendition in self.renditions {
+			if rendition.decimation == 0 {
+				return Err(Error::OutOfRange);
+			}
+
+			// Keyframes are decided by the SimulcastEncoder so they line up.
+			let mut config = rendition.config;
+			config.max_gop_duration = None;
+
+			let (encoder, output) = config.init()?;
+			encoders.push((encoder, rendition.decimation));
+			encoded.push(output);
+		}
+
+		let encoder = SimulcastEncoder {
+			encoders,
+			max_gop_duration: self.max_gop_duration,
+			last_keyframe: None,
+			tick: 0,
+		};
+
+		let encoded = SimulcastEncoded {
+			finished: vec![false; encoded.len()],
+			encoded,
+			next: 0,
+		};
+
+		Ok((encoder, encoded))
+	}
+}
+
+/// Encodes each [VideoFrame] into multiple renditions, with keyframes at the same timestamps.
+pub struct SimulcastEncoder {
+	encoders: Vec<(VideoEncoder, u32)>,
+	max_gop_duration: Option<Duration>,
+	last_keyframe: Option<Timestamp>,
+
+	// The number of frames since the last keyframe, used for decimation.
+	tick: u64,
+}
+
+impl SimulcastEncoder {
+	/// Encode the frame for every rendition, unless skipped by decimation.
+	///
+	/// Keyframes are encoded by every rendition regardless of decimation.
+	pub fn encode(&mut self, frame: &VideoFrame, options: VideoEncodeOptions) -> Result<(), Error> {
+		let timestamp = frame.timestamp();
+
+		let key_frame = match options.key_frame {
+			Some(key_frame) => key_frame,
+			None => match (self.last_keyframe, self.max_gop_duration) {
+				(None, _) => true,
+				(Some(last), Some(max)) => timestamp.saturating_sub(last) >= max,
+				(Some(_), None) => false,
+			},
+		};
+
+		if key_frame {
+			self.last_keyframe = Some(timestamp);
+			self.tick = 0;
+		}
+
+		for (encoder, decimation) in self.encoders.iter_mut() {
+			if self.tick % *decimation as u64 != 0 {
+				continue;
+			}
+
+			let options = VideoEncodeOptions {
+				key_frame: Some(key_frame),
+			};
+			encoder.encode(frame, options)?;
+		}
+
+		self.tick += 1;
+
+		Ok(())
+	}
+
+	/// Change the config of a single rendition, ex. in response to an [super::AbrController].
+	///
+	/// Changing the resolution or codec requires a keyframe, so the next frame is a keyframe for every rendition.
+	pub fn reconfigure(&mut self, rendition: usize, mut config: VideoEncoderConfig) -> Result<(), Error> {
+		let (encoder, _) = self.encoders.get_mut(rendition).ok_or(Error::OutOfRange)?;
+
+		let current = encoder.config();
+		let keyframe = config.resolution != current.resolution || config.codec != current.codec;
+
+		config.max_gop_duration = None;
+		encoder.reconfigure(config)?;
+
+		// Keep the keyframes aligned across renditions.
+		if keyframe {
+			self.last_keyframe = None;
+		}
+
+		Ok(())
+	}
+
+	pub fn config(&self, rendition: usize) -> Option<&VideoEncoderConfig> {
+		Some(self.encoders.get(rendition)?.0.config())
+	}
+
+	/// The number of renditions.
+	pub fn len(&self) -> usize {
+		self.encoders.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.encoders.is_empty()
+	}
+
+	/// The largest queue size of any rendition.
+	pub fn queue_size(&self) -> u32 {
+		self.encoders
+			.iter()
+			.map(|(encoder, _)| encoder.queue_size())
+			.max()
+			.unwrap_or(0)
+	}
+
+	pub async fn flush(&mut self) -> Result<(), Error> {
+		for (encoder, _) in self.encoders.iter_mut() {
+			encoder.flush().await?;
+		}
+
+		Ok(())
+	}
+}
+
+/// An [EncodedFrame] tagged with the index of its rendition.
+#[derive(Debug)]
+pub struct SimulcastFrame {
+	pub rendition: usize,
+	pub frame: EncodedFrame,
+}
+
+pub struct SimulcastEncoded {
+	encoded: Vec<VideoEncoded>,
+
+	// Set once the rendition has returned None.
+	finished: Vec<bool>,
+
+	// The rendition to poll first, so a busy rendition doesn't starve the others.
+	next: usize,
+}
+
+type PendingFrame<'a> = Pin<Box<dyn Future<Output = Result<Option<EncodedFrame>, Error>> + 'a>>;
+
+impl SimulcastEncoded {
+	/// Returns the next frame from any rendition, or None when they've all finished.
+	pub async fn frame(&mut self) -> Result<Option<SimulcastFrame>, Error> {
+		let count = self.encoded.len();
+
+		let mut pending: Vec<(usize, PendingFrame)> = self
+			.encoded
+			.iter_mut()
+			.zip(&self.finished)
+			.enumerate()
+			.filter(|(_, (_, finished))| !**finished)
+			.map(|(rendition, (encoded, _))| (rendition, Box::pin(encoded.frame()) as PendingFrame))
+			.collect();
+
+		let start = pending.iter().position(|(rendition, _)| *rendition >= self.next);
+		pending.rotate_left(start.unwrap_or(0));
+
+		let mut finished = Vec::new();
+
+		let result = std::future::poll_fn(|cx| {
+			let mut i = 0;
+
+			while i < pending.len() {
+				let (rendition, future) = &mut pending[i];
+
+				match future.as_mut().poll(cx) {
+					Poll::Ready(Ok(Some(frame))) => {
+						let frame = SimulcastFrame {
+							rendition: *rendition,
+							frame,
+						};
+						return Poll::Ready(Ok(Some(frame)));
+					}
+					Poll::Ready(Ok(None)) => {
+						finished.push(*rendition);
+						drop(pending.remove(i));
+					}
+					Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+					Poll::Pending => i += 1,
+				}
+			}
+
+			match pending.is_empty() {
+				true => Poll::Ready(Ok(None)),
+				false => Poll::Pending,
+			}
+		})
+		.await;
+
+		drop(pending);
+
+		for rendition in finished {
+			self.finished[rendition] = true;
+		}
+
+		if let Ok(Some(frame)) = &result {
+			self.next = (frame.rendition + 1) % count;
+		}
+
+		result
+	}
+
+	/// Returns the decoder config for the rendition, after its first frame has been encoded.
+	pub fn config(&self, rendition: usize) -> Option<VideoDecoderConfig> {
+		self.encoded.get(rendition)?.config()
+	}
+
+	/// The number of renditions.
+	pub fn len(&self) -> usize {
+		self.encoded.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.encoded.is_empty()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn ladder() {
+		let config = SimulcastConfig::ladder("vp8", Dimensions::new(1280, 720), 30.0, 3);
+
+		let renditions: Vec<_> = config
+			.renditions
+			.iter()
+			.map(|r| (r.config.resolution, r.config.bitrate.unwrap(), r.decimation))
+			.collect();
+
+		assert_eq!(
+			renditions,
+			[
+				(Dimensions::new(1280, 720), 2_764_800, 1),
+				(Dimensions::new(640, 360), 691_200, 1),
+				(Dimensions::new(320, 180), 86_400, 2),
+			]
+		);
+
+		// Stop before getting too small.
+		let config = SimulcastConfig::ladder("vp8", Dimensions::new(320, 240), 30.0, 3);
+		assert_eq!(config.renditions.len(), 2);
+		assert_eq!(config.renditions[1].config.resolution, Dimensions::new(160, 120));
+	}
+
+	#[cfg(mock)]
+	#[tokio::test]
+	async fn encode() {
+		use crate::VideoPixelFormat;
+
+		let config = SimulcastConfig {
+			max_gop_duration: Some(Duration::from_secs(1)),
+			..SimulcastConfig::ladder("vp8", Dimensions::new(1280, 720), 4.0, 3)
+		};
+
+		let (mut encoder, mut encoded) = config.init().unwrap();
+		assert_eq!(encoder.len(), 3);
+
+		// The mock encoder doesn't scale, so use a tiny frame.
+		let data = [0; 6];
+		for i in 0..8 {
+			let frame = VideoFrame::new(
+				&data,
+				VideoPixelFormat::I420,
+				Dimensions::new(2, 2),
+				Duration::from_millis(i * 250),
+			)
+			.unwrap();
+			encoder.encode(&frame, Default::default()).unwrap();
+		}
+		drop(encoder);
+
+		let mut frames = vec![Vec::new(); 3];
+		while let Some(frame) = encoded.frame().await.unwrap() {
+			let SimulcastFrame { rendition, frame } = frame;
+			frames[rendition].push((frame.timestamp.as_millis(), frame.keyframe));
+		}
+
+		let full: Vec<_> = (0..8).map(|i| (i * 250, i % 4 == 0)).collect();
+		assert_eq!(frames[0], full);
+		assert_eq!(frames[1], full);
+		assert_eq!(frames[2], [(0, true), (500, false), (1000, true), (1500, false)]);
+
+		assert_eq!(encoded.config(1).unwrap().resolution, Some(Dimensions::new(640, 360)));
+	}
+
+	#[cfg(mock)]
+	#[tokio::test]
+	async fn reconfigure() {
+		use crate::VideoPixelFormat;
+
+		let config = SimulcastConfig::ladder("vp8", Dimensions::new(1280, 720), 30.0, 2);
+		let (mut encoder, mut encoded) = config.init().unwrap();
+
+		let data = [0; 6];
+		for i in 0..4 {
+			// Changing the resolution of one rendition forces a keyframe for all of them.
+			if i == 2 {
+				let mut config = encoder.config(1).unwrap().clone();
+				config.resolution = Dimensions::new(320, 180);
+				encoder.reconfigure(1, config).unwrap();
+			}
+
+			let frame = VideoFrame::new(
+				&data,
+				VideoPixelFormat::I420,
+				Dimensions::new(2, 2),
+				Duration::from_millis(i * 33),
+			)
+			.unwrap();
+			encoder.encode(&frame, Default::default()).unwrap();
+		}
+		drop(encoder);
+
+		let mut frames = vec![Vec::new(); 2];
+		while let Some(frame) = encoded.frame().await.unwrap() {
+			frames[frame.rendition].push(frame.frame.keyframe);
+		}
+
+		assert_eq!(frames[0], [true, false, true, false]);
+		assert_eq!(frames[1], [true, false, true, false]);
+	}
+}
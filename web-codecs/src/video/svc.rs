@@ -0,0 +1,210 @@
+use std::{fmt, str::FromStr};
+
+use crate::{EncodedFrame, Error};
+
+macro_rules! scalability_modes {
+	($($mode:ident => $name:literal,)*) => {
+		/// A scalability mode from the [WebRTC SVC registry](https://www.w3.org/TR/webrtc-svc/#scalabilitymodes*).
+		///
+		/// `LxTy` uses x spatial and y temporal layers, `SxTy` uses independent (simulcast-like) spatial layers,
+		/// `h` uses a 1.5:1 spatial ratio instead of 2:1, and `_KEY` only predicts across spatial layers on keyframes.
+		#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+		pub enum ScalabilityMode {
+			$($mode,)*
+		}
+
+		impl ScalabilityMode {
+			pub const ALL: &[ScalabilityMode] = &[$(Self::$mode,)*];
+
+			pub fn as_str(&self) -> &'static str {
+				match self {
+					$(Self::$mode => $name,)*
+				}
+			}
+		}
+	};
+}
+
+scalability_modes! {
+	L1T1 => "L1T1",
+	L1T2 => "L1T2",
+	L1T3 => "L1T3",
+	L2T1 => "L2T1",
+	L2T2 => "L2T2",
+	L2T3 => "L2T3",
+	L3T1 => "L3T1",
+	L3T2 => "L3T2",
+	L3T3 => "L3T3",
+	L2T1h => "L2T1h",
+	L2T2h => "L2T2h",
+	L2T3h => "L2T3h",
+	L3T1h => "L3T1h",
+	L3T2h => "L3T2h",
+	L3T3h => "L3T3h",
+	S2T1 => "S2T1",
+	S2T2 => "S2T2",
+	S2T3 => "S2T3",
+	S2T1h => "S2T1h",
+	S2T2h => "S2T2h",
+	S2T3h => "S2T3h",
+	S3T1 => "S3T1",
+	S3T2 => "S3T2",
+	S3T3 => "S3T3",
+	S3T1h => "S3T1h",
+	S3T2h => "S3T2h",
+	S3T3h => "S3T3h",
+	L2T1Key => "L2T1_KEY",
+	L2T2Key => "L2T2_KEY",
+	L2T3Key => "L2T3_KEY",
+	L3T1Key => "L3T1_KEY",
+	L3T2Key => "L3T2_KEY",
+	L3T3Key => "L3T3_KEY",
+	L2T2KeyShift => "L2T2_KEY_SHIFT",
+	L2T3KeyShift => "L2T3_KEY_SHIFT",
+	L3T2KeyShift => "L3T2_KEY_SHIFT",
+	L3T3KeyShift => "L3T3_KEY_SHIFT",
+}
+
+impl ScalabilityMode {
+	pub fn spatial_layers(&self) -> u8 {
+		self.as_str().as_bytes()[1] - b'0'
+	}
+
+	pub fn temporal_layers(&self) -> u8 {
+		self.as_str().as_bytes()[3] - b'0'
+	}
+
+	/// The temporal layer of the Nth frame after a keyframe, using the usual dyadic pattern.
+	///
+	/// ex. L1T3 produces 0, 2, 1, 2, 0, 2, 1, 2...
+	pub fn temporal_layer(&self, index: u64) -> u8 {
+		match self.temporal_layers() {
+			2 => (index % 2) as u8,
+			3 => [0, 2, 1, 2][(index % 4) as usize],
+			_ => 0,
+		}
+	}
+}
+
+impl fmt::Display for ScalabilityMode {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+impl FromStr for ScalabilityMode {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::ALL
+			.iter()
+			.find(|mode| mode.as_str() == s)
+			.copied()
+			.ok_or(Error::UnsupportedFormat)
+	}
+}
+
+/// Drops frames above a temporal layer, ex. to forward a lower frame rate to a constrained receiver.
+///
+/// Lowering the layer takes effect immediately, since lower layers never reference higher ones.
+/// Raising the layer waits for the next base layer frame, otherwise the first forwarded frames could
+/// reference frames that were dropped. Frames without a temporal layer are always forwarded.
+#[derive(Debug, Clone)]
+pub struct TemporalLayerFilter {
+	// The layer currently forwarded.
+	current: u8,
+
+	// The requested layer, applied at the next switching point.
+	target: u8,
+}
+
+impl TemporalLayerFilter {
+	pub fn new(max_layer: u8) -> Self {
+		Self {
+			current: max_layer,
+			target: max_layer,
+		}
+	}
+
+	/// The highest layer currently forwarded, which may lag behind [Self::set_max_layer].
+	pub fn max_layer(&self) -> u8 {
+		self.current
+	}
+
+	pub fn set_max_layer(&mut self, layer: u8) {
+		self.target = layer;
+		if layer < self.current {
+			self.current = layer;
+		}
+	}
+
+	/// Returns the frame if it should be forwarded.
+	pub fn filter(&mut self, frame: EncodedFrame) -> Option<EncodedFrame> {
+		let Some(layer) = frame.temporal_layer else {
+			return Some(frame);
+		};
+
+		if frame.keyframe || layer == 0 {
+			self.current = self.target;
+		}
+
+		(layer <= self.current).then_some(frame)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::time::Duration;
+
+	use bytes::Bytes;
+
+	use super::*;
+
+	#[test]
+	fn mode() {
+		for mode in ScalabilityMode::ALL {
+			assert_eq!(mode.to_string().parse::<ScalabilityMode>().unwrap(), *mode);
+		}
+
+		let mode: ScalabilityMode = "L3T2_KEY".parse().unwrap();
+		assert_eq!(mode, ScalabilityMode::L3T2Key);
+		assert_eq!(mode.spatial_layers(), 3);
+		assert_eq!(mode.temporal_layers(), 2);
+
+		assert!("L4T4".parse::<ScalabilityMode>().is_err());
+	}
+
+	#[test]
+	fn filter() {
+		let layers: Vec<u8> = (0..12).map(|i| ScalabilityMode::L1T3.temporal_layer(i)).collect();
+		assert_eq!(layers, [0, 2, 1, 2, 0, 2, 1, 2, 0, 2, 1, 2]);
+
+		let mut filter = TemporalLayerFilter::new(2);
+		let mut forwarded = Vec::new();
+
+		for (i, layer) in layers.into_iter().enumerate() {
+			match i {
+				2 => filter.set_max_layer(0),
+				5 => filter.set_max_layer(1),
+				_ => {}
+			}
+
+			let frame = EncodedFrame {
+				payload: Bytes::new().into(),
+				timestamp: Duration::from_millis(i as u64),
+				keyframe: i == 0,
+				temporal_layer: Some(layer),
+				alpha: None,
+				config_changed: false,
+			};
+
+			if let Some(frame) = filter.filter(frame) {
+				forwarded.push(frame.timestamp.as_millis());
+			}
+		}
+
+		// Down to the base layer immediately, but back up only at the next base layer frame (8).
+		assert_eq!(forwarded, [0, 1, 4, 8, 10]);
+		assert_eq!(filter.max_layer(), 1);
+	}
+}
@@ -0,0 +1,315 @@
+use std::io::{Read, Write};
+
+use crate::{allocation_size, Error, Result, Timestamp};
+
+use super::{Dimensions, VideoFrame, VideoPixelFormat};
+
+// The largest frame we're willing to allocate, enough for 8K I444.
+const MAX_FRAME_SIZE: usize = 256 * 1024 * 1024;
+
+/// The stream parameters of a YUV4MPEG2 (Y4M) file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Y4mHeader {
+	pub dimensions: Dimensions,
+
+	/// The chroma subsampling; only I420, I422 and I444 are supported.
+	pub format: VideoPixelFormat,
+
+	/// The frame rate as a fraction, ex. (30000, 1001) for 29.97 fps.
+	pub framerate: (u32, u32),
+}
+
+impl Y4mHeader {
+	fn parse(line: &str) -> Result<Self> {
+		let mut params = line.split(' ');
+		if params.next() != Some("YUV4MPEG2") {
+			return Err(Error::InvalidHeader);
+		}
+
+		let mut width = None;
+		let mut height = None;
+		let mut framerate = None;
+
+		// The default colorspace when none is specified.
+		let mut format = VideoPixelFormat::I420;
+
+		for param in params.filter(|param| !param.is_empty()) {
+			let (key, value) = param.split_at_checked(1).ok_or(Error::InvalidHeader)?;
+
+			match key {
+				"W" => width = value.parse().ok(),
+				"H" => height = value.parse().ok(),
+				"F" => {
+					let (num, den) = value.split_once(':').ok_or(Error::InvalidHeader)?;
+					framerate = num.parse().ok().zip(den.parse().ok());
+				}
+				"C" => {
+					format = match value {
+						"420" | "420jpeg" | "420paldv" | "420mpeg2" => VideoPixelFormat::I420,
+						"422" => VideoPixelFormat::I422,
+						"444" => VideoPixelFormat::I444,
+						_ => return Err(Error::UnsupportedFormat),
+					}
+				}
+				// Interlacing, aspect ratio, and extensions are ignored.
+				_ => {}
+			}
+		}
+
+		let dimensions = Dimensions::new(width.ok_or(Error::InvalidHeader)?, height.ok_or(Error::InvalidHeader)?);
+		if dimensions.width == 0 || dimensions.height == 0 {
+			return Err(Error::InvalidDimensions);
+		}
+
+		let framerate = framerate.ok_or(Error::InvalidHeader)?;
+		if framerate.0 == 0 || framerate.1 == 0 {
+			return Err(Error::InvalidHeader);
+		}
+
+		let header = Self {
+			dimensions,
+			format,
+			framerate,
+		};
+
+		// Otherwise a corrupt header could make us allocate an absurd amount of memory.
+		header.frame_size()?;
+
+		Ok(header)
+	}
+
+	fn encode(&self) -> Result<String> {
+		let colorspace = match self.format {
+			VideoPixelFormat::I420 => "420jpeg",
+			VideoPixelFormat::I422 => "422",
+			VideoPixelFormat::I444 => "444",
+			_ => return Err(Error::UnsupportedFormat),
+		};
+
+		Ok(format!(
+			"YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C{}\n",
+			self.dimensions.width, self.dimensions.height, self.framerate.0, self.framerate.1, colorspace
+		))
+	}
+
+	// The size of each frame, rejecting dimensions that overflow or exceed MAX_FRAME_SIZE.
+	fn frame_size(&self) -> Result<usize> {
+		allocation_size(self.format, self.dimensions)
+			.filter(|&size| size <= MAX_FRAME_SIZE)
+			.ok_or(Error::InvalidDimensions)
+	}
+
+	pub(crate) fn timestamp(&self, index: u64) -> Timestamp {
+		let (num, den) = self.framerate;
+
+		// Computed in u128 so long files with large denominators don't overflow.
+		let micros = index as u128 * 1_000_000 * den as u128 / num as u128;
+		Timestamp::from_micros(micros.try_into().unwrap_or(u64::MAX))
+	}
+}
+
+/// Reads raw frames from a Y4M file, timestamped using the frame rate.
+///
+/// The header is read a byte at a time, so consider wrapping files in a [std::io::BufReader].
+pub struct Y4mReader<R: Read> {
+	reader: R,
+	header: Y4mHeader,
+	index: u64,
+}
+
+impl<R: Read> Y4mReader<R> {
+	pub fn new(mut reader: R) -> Result<Self> {
+		let line = read_line(&mut reader)?.ok_or(Error::InvalidHeader)?;
+		let header = Y4mHeader::parse(&line)?;
+
+		Ok(Self {
+			reader,
+			header,
+			index: 0,
+		})
+	}
+
+	pub fn header(&self) -> &Y4mHeader {
+		&self.header
+	}
+
+	/// Read the next frame, returning None at the end of the file.
+	pub fn read(&mut self) -> Result<Option<VideoFrame>> {
+		let Some(line) = read_line(&mut self.reader)? else {
+			return Ok(None);
+		};
+
+		if line.split(' ').next() != Some("FRAME") {
+			return Err(Error::InvalidHeader);
+		}
+
+		let mut data = vec![0; self.header.frame_size()?];
+		self.reader.read_exact(&mut data)?;
+
+		let timestamp = self.header.timestamp(self.index);
+		self.index += 1;
+
+		let frame = VideoFrame::new(&data, self.header.format, self.header.dimensions, timestamp)?;
+		Ok(Some(frame))
+	}
+
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+}
+
+/// Writes raw frames to a Y4M file.
+///
+/// Frame timestamps are not stored; the file uses the constant frame rate in the header.
+pub struct Y4mWriter<W: Write> {
+	writer: W,
+	header: Y4mHeader,
+	buffer: Vec<u8>,
+}
+
+impl<W: Write> Y4mWriter<W> {
+	pub fn new(mut writer: W, header: Y4mHeader) -> Result<Self> {
+		let encoded = header.encode()?;
+		header.frame_size()?;
+
+		writer.write_all(encoded.as_bytes())?;
+
+		Ok(Self {
+			writer,
+			header,
+			buffer: Vec::new(),
+		})
+	}
+
+	pub fn header(&self) -> &Y4mHeader {
+		&self.header
+	}
+
+	/// Write a frame, which must match the dimensions and format of the header.
+	pub async fn write(&mut self, frame: &VideoFrame) -> Result<()> {
+		if frame.dimensions() != self.header.dimensions {
+			return Err(Error::InvalidDimensions);
+		}
+
+		if frame.format() != Some(self.header.format) {
+			return Err(Error::UnsupportedFormat);
+		}
+
+		self.buffer.resize(self.header.frame_size()?, 0);
+		frame.copy_to(&mut self.buffer).await?;
+
+		self.writer.write_all(b"FRAME\n")?;
+		self.writer.write_all(&self.buffer)?;
+
+		Ok(())
+	}
+
+	pub fn into_inner(self) -> W {
+		self.writer
+	}
+}
+
+// Read a newline terminated header, returning None if the reader is already at EOF.
+fn read_line<R: Read>(reader: &mut R) -> Result<Option<String>> {
+	// The longest header we're willing to parse.
+	const MAX_SIZE: usize = 1024;
+
+	let mut line = Vec::new();
+	let mut byte = [0];
+
+	loop {
+		if reader.read(&mut byte)? == 0 {
+			return match line.is_empty() {
+				true => Ok(None),
+				false => Err(Error::InvalidHeader),
+			};
+		}
+
+		match byte[0] {
+			b'\n' => break,
+			_ if line.len() >= MAX_SIZE => return Err(Error::InvalidHeader),
+			b => line.push(b),
+		}
+	}
+
+	String::from_utf8(line).map(Some).map_err(|_| Error::InvalidHeader)
+}
+
+#[cfg(all(test, mock))]
+mod test {
+	use std::time::Duration;
+
+	use super::*;
+
+	#[tokio::test]
+	async fn roundtrip() {
+		let header = Y4mHeader {
+			dimensions: Dimensions::new(4, 2),
+			format: VideoPixelFormat::I420,
+			framerate: (30000, 1001),
+		};
+
+		let mut writer = Y4mWriter::new(Vec::new(), header).unwrap();
+		for i in 0..3 {
+			let data: Vec<u8> = (0..12).map(|b| b + i).collect();
+			let frame = VideoFrame::new(&data, header.format, header.dimensions, Duration::ZERO).unwrap();
+			writer.write(&frame).await.unwrap();
+		}
+
+		let file = writer.into_inner();
+		assert!(file.starts_with(b"YUV4MPEG2 W4 H2 F30000:1001 Ip A1:1 C420jpeg\nFRAME\n"));
+		assert_eq!(file.len(), 45 + 3 * (6 + 12));
+
+		let mut reader = Y4mReader::new(file.as_slice()).unwrap();
+		assert_eq!(reader.header(), &header);
+
+		for i in 0..3 {
+			let frame = reader.read().unwrap().unwrap();
+			assert_eq!(frame.timestamp(), Duration::from_micros(i * 1_000_000 * 1001 / 30000));
+
+			let mut data = vec![0; 12];
+			frame.copy_to(&mut data).await.unwrap();
+			assert_eq!(data, (0..12).map(|b| b + i as u8).collect::<Vec<_>>());
+		}
+
+		assert!(reader.read().unwrap().is_none());
+	}
+
+	#[test]
+	fn header() {
+		let header = Y4mHeader::parse("YUV4MPEG2 W1920 H1080 F25:1 It A0:0 C444 XYSCSS=444").unwrap();
+		assert_eq!(header.dimensions, Dimensions::new(1920, 1080));
+		assert_eq!(header.format, VideoPixelFormat::I444);
+		assert_eq!(header.framerate, (25, 1));
+
+		assert!(matches!(
+			Y4mHeader::parse("YUV4MPEG2 W2 H2 F25:1 Cmono"),
+			Err(Error::UnsupportedFormat)
+		));
+		assert!(matches!(
+			Y4mHeader::parse("YUV4MPEG2 W2 F25:1"),
+			Err(Error::InvalidHeader)
+		));
+		assert!(matches!(
+			Y4mHeader::parse("YUV4MPEG2 W4294967295 H4294967295 F25:1"),
+			Err(Error::InvalidDimensions)
+		));
+		assert!(matches!(
+			Y4mHeader::parse("YUV4MPEG2 W65536 H65536 F25:1 C444"),
+			Err(Error::InvalidDimensions)
+		));
+	}
+
+	#[test]
+	fn timestamp() {
+		let header = Y4mHeader {
+			dimensions: Dimensions::new(2, 2),
+			format: VideoPixelFormat::I420,
+			framerate: (1, u32::MAX),
+		};
+
+		let index = u64::MAX / u32::MAX as u64;
+		assert_eq!(header.timestamp(1), Duration::from_micros(1_000_000 * u32::MAX as u64));
+		assert_eq!(header.timestamp(index), Duration::from_micros(u64::MAX));
+	}
+}
@@ -0,0 +1,247 @@
+use std::{
+	future::{poll_fn, Future},
+	pin::Pin,
+	task::{ready, Context, Poll},
+};
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+	js_sys::{self, ArrayBuffer, Uint8Array},
+	ReadableStream, ReadableStreamByobReader, ReadableStreamReadResult,
+};
+
+use crate::{Error, PromiseExt};
+
+/// A wrapper around a byte ReadableStream using a BYOB (bring your own buffer) reader.
+///
+/// Unlike [crate::Reader], the same JS buffer is reused for every read instead of allocating a new one per chunk.
+/// The stream must be a byte stream, ex. a fetch response body or a WebTransport stream.
+pub struct ByteReader {
+	inner: ReadableStreamByobReader,
+
+	// The pending read, which owns the JS buffer until it resolves.
+	read: Option<JsFuture>,
+
+	// The JS buffer reused across reads, or None while a read is pending.
+	buffer: Option<ArrayBuffer>,
+
+	// Bytes that didn't fit in the caller's buffer, and the offset of the next unread byte.
+	// Only possible when a canceled read is resumed with a smaller buffer.
+	leftover: Option<(Uint8Array, u32)>,
+}
+
+impl ByteReader {
+	/// Grab a lock on the given byte stream until dropped.
+	pub fn new(stream: &ReadableStream) -> Result<Self, Error> {
+		if stream.locked() {
+			return Err(Error::Locked);
+		}
+
+		let inner = ReadableStreamByobReader::new(stream)?;
+		Ok(Self {
+			inner,
+			read: None,
+			buffer: None,
+			leftover: None,
+		})
+	}
+
+	/// Read up to `buf.len()` bytes, returning 0 if the stream is done.
+	///
+	/// This is cancel-safe: if the future is dropped, the pending read is resumed by the next call.
+	pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+		poll_fn(|cx| self.poll_read(cx, buf, 1)).await
+	}
+
+	/// Read at least `min` bytes, or fewer only if the stream is done.
+	///
+	/// Use `min = buf.len()` to fill the entire buffer, like `read_exact`.
+	pub async fn read_min(&mut self, buf: &mut [u8], min: usize) -> Result<usize, Error> {
+		let min = min.min(buf.len());
+		let mut size = 0;
+
+		// Browsers that don't support the `min` option may return fewer bytes.
+		while size < min {
+			match poll_fn(|cx| self.poll_read(cx, &mut buf[size..], min - size)).await? {
+				0 => break,
+				n => size += n,
+			}
+		}
+
+		Ok(size)
+	}
+
+	/// Poll for up to `buf.len()` bytes, asking the stream to wait for at least `min` bytes.
+	///
+	/// Returns 0 if the stream is done or `buf` is empty.
+	pub fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8], min: usize) -> Poll<Result<usize, Error>> {
+		if buf.is_empty() {
+			return Poll::Ready(Ok(0));
+		}
+
+		if let Some((view, offset)) = self.leftover.take() {
+			return Poll::Ready(Ok(self.copy(view, offset, buf)));
+		}
+
+		let read = match &mut self.read {
+			Some(read) => read,
+			None => {
+				let view = self.view(buf.len());
+				let promise = read_with_min(&self.inner, &view, min.clamp(1, buf.len()));
+				self.read.insert(JsFuture::from(promise))
+			}
+		};
+
+		let result = ready!(Pin::new(read).poll(cx));
+		self.read.take(); // Clear the promise once resolved
+
+		let result: ReadableStreamReadResult = result?.unchecked_into();
+
+		// The value is undefined if the stream was canceled, otherwise it's a view of our buffer.
+		let view = result.get_value().dyn_into::<Uint8Array>().ok();
+
+		if result.get_done().unwrap_or(false) {
+			self.buffer = view.map(|view| view.buffer());
+			return Poll::Ready(Ok(0));
+		}
+
+		let Some(view) = view else {
+			return Poll::Ready(Err(Error::TypeError {
+				message: "BYOB read returned a non-Uint8Array chunk".to_string(),
+				stack: None,
+			}));
+		};
+
+		Poll::Ready(Ok(self.copy(view, 0, buf)))
+	}
+
+	// Return a view to read into, reusing (and growing) the buffer from the previous read.
+	fn view(&mut self, size: usize) -> Uint8Array {
+		let buffer = match self.buffer.take() {
+			Some(buffer) if buffer.byte_length() as usize >= size => buffer,
+			_ => ArrayBuffer::new(size as u32),
+		};
+
+		Uint8Array::new_with_byte_offset_and_length(&buffer, 0, size as u32)
+	}
+
+	// Copy as much as fits into `buf`, keeping the rest for the next read.
+	fn copy(&mut self, view: Uint8Array, offset: u32, buf: &mut [u8]) -> usize {
+		let available = view.length() - offset;
+		let size = buf.len().min(available as usize);
+		view.subarray(offset, offset + size as u32).copy_to(&mut buf[..size]);
+
+		if (size as u32) < available {
+			self.leftover = Some((view, offset + size as u32));
+		} else {
+			self.buffer = Some(view.buffer());
+		}
+
+		size
+	}
+
+	/// Abort the stream early with the given reason.
+	pub fn abort(&mut self, reason: &str) {
+		let str = JsValue::from_str(reason);
+		self.inner.cancel_with_reason(&str).ignore();
+	}
+
+	pub async fn closed(&self) -> Result<(), Error> {
+		JsFuture::from(self.inner.closed()).await?;
+		Ok(())
+	}
+}
+
+impl Drop for ByteReader {
+	/// Release the lock
+	fn drop(&mut self) {
+		self.inner.release_lock();
+	}
+}
+
+fn read_with_min(reader: &ReadableStreamByobReader, view: &Uint8Array, min: usize) -> js_sys::Promise {
+	let options = ReadOptions::new();
+	options.set_min(min as u32);
+	reader.unchecked_ref::<ByobReader>().read(view, &options)
+}
+
+// The `min` option is not in web-sys yet.
+#[wasm_bindgen]
+extern "C" {
+	#[wasm_bindgen(extends = js_sys::Object)]
+	type ReadOptions;
+
+	#[wasm_bindgen(method, setter = min)]
+	fn set_min(this: &ReadOptions, min: u32);
+
+	type ByobReader;
+
+	#[wasm_bindgen(method)]
+	fn read(this: &ByobReader, view: &Uint8Array, options: &ReadOptions) -> js_sys::Promise;
+}
+
+impl ReadOptions {
+	fn new() -> Self {
+		js_sys::Object::new().unchecked_into()
+	}
+}
+
+#[cfg(feature = "tokio")]
+mod tokio_impl {
+	use super::*;
+
+	use std::io;
+	use tokio::io::{AsyncRead, ReadBuf};
+
+	impl AsyncRead for ByteReader {
+		fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+			let size = ready!(self.get_mut().poll_read(cx, buf.initialize_unfilled(), 1))?;
+			buf.advance(size);
+
+			Poll::Ready(Ok(()))
+		}
+	}
+}
+
+#[cfg(feature = "futures-io")]
+mod futures_impl {
+	use super::*;
+
+	use futures_io::AsyncRead;
+	use std::io;
+
+	impl AsyncRead for ByteReader {
+		fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+			let size = ready!(self.get_mut().poll_read(cx, buf, 1))?;
+			Poll::Ready(Ok(size))
+		}
+	}
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod test {
+	use super::*;
+
+	use futures::stream;
+	use wasm_bindgen_test::wasm_bindgen_test;
+
+	use crate::{readable_bytes_from_stream, QueuingStrategy};
+
+	#[wasm_bindgen_test]
+	async fn read_min() {
+		let chunks = [vec![1, 2], vec![3, 4, 5], vec![6]];
+		let stream = readable_bytes_from_stream(stream::iter(chunks), &QueuingStrategy::Count(0.0)).unwrap();
+		let mut reader = ByteReader::new(&stream).unwrap();
+
+		// The first chunk alone isn't enough, so the read waits for the next one.
+		let mut buf = [0; 8];
+		let size = reader.read_min(&mut buf, 4).await.unwrap();
+		assert!(size >= 4, "{size}");
+
+		// Asking for more than is left returns early at the end of the stream.
+		let size = size + reader.read_min(&mut buf[size..], 8).await.unwrap();
+		assert_eq!(buf[..size], [1, 2, 3, 4, 5, 6]);
+		assert_eq!(reader.read(&mut buf).await.unwrap(), 0);
+	}
+}
@@ -1,13 +1,172 @@
+use std::io;
+
 use wasm_bindgen::prelude::*;
+use web_sys::js_sys;
 
 #[derive(Debug, thiserror::Error, Clone)]
 pub enum Error {
-	#[error("unknown error: {0:?}")]
-	Unknown(JsValue),
+	/// The stream was closed, so no more chunks can be written.
+	#[error("closed")]
+	Closed,
+
+	/// The writable stream was aborted with the given reason.
+	#[error("aborted: {}", describe(.0))]
+	Aborted(JsValue),
+
+	/// The readable stream or pipe was canceled with the given reason.
+	#[error("canceled: {}", describe(.0))]
+	Canceled(JsValue),
+
+	/// The stream is already locked to another reader or writer.
+	#[error("locked")]
+	Locked,
+
+	/// A JS TypeError, ex. writing a chunk the stream doesn't accept.
+	#[error("type error: {message}")]
+	TypeError { message: String, stack: Option<String> },
+
+	/// Any other JS error, or a value thrown that isn't an Error.
+	#[error("{name}: {message}")]
+	Other {
+		name: String,
+		message: String,
+		stack: Option<String>,
+	},
 }
 
 impl From<JsValue> for Error {
 	fn from(e: JsValue) -> Self {
-		Self::Unknown(e)
+		let name = property(&e, "name");
+		let message = property(&e, "message");
+
+		match (name, message) {
+			(Some(name), Some(message)) => match name.as_str() {
+				"TypeError" => Self::TypeError {
+					stack: property(&e, "stack"),
+					message,
+				},
+				"AbortError" => Self::Aborted(e),
+				_ => Self::Other {
+					stack: property(&e, "stack"),
+					name,
+					message,
+				},
+			},
+			_ => Self::Other {
+				name: "Error".to_string(),
+				message: describe(&e),
+				stack: None,
+			},
+		}
+	}
+}
+
+impl From<Error> for JsValue {
+	fn from(e: Error) -> Self {
+		match e {
+			Error::Aborted(reason) | Error::Canceled(reason) => reason,
+			Error::TypeError { message, .. } => js_sys::TypeError::new(&message).into(),
+			Error::Closed | Error::Locked => js_sys::TypeError::new(&e.to_string()).into(),
+			Error::Other { name, message, .. } => {
+				let err = js_sys::Error::new(&message);
+				err.set_name(&name);
+				err.into()
+			}
+		}
+	}
+}
+
+impl From<Error> for io::Error {
+	fn from(err: Error) -> Self {
+		let kind = match &err {
+			Error::Closed => io::ErrorKind::BrokenPipe,
+			Error::Aborted(_) | Error::Canceled(_) => io::ErrorKind::ConnectionAborted,
+			Error::Locked => io::ErrorKind::ResourceBusy,
+			Error::TypeError { .. } => io::ErrorKind::InvalidInput,
+			Error::Other { name, .. } => match name.as_str() {
+				"NetworkError" => io::ErrorKind::ConnectionReset,
+				"TimeoutError" => io::ErrorKind::TimedOut,
+				"NotAllowedError" | "SecurityError" => io::ErrorKind::PermissionDenied,
+				"QuotaExceededError" => io::ErrorKind::QuotaExceeded,
+				_ => io::ErrorKind::Other,
+			},
+		};
+
+		// JsValue is not Send, so only the message is kept.
+		io::Error::new(kind, err.to_string())
+	}
+}
+
+// Returns a string property of an object, if it exists.
+fn property(value: &JsValue, key: &str) -> Option<String> {
+	if !value.is_object() {
+		return None;
+	}
+
+	js_sys::Reflect::get(value, &JsValue::from_str(key)).ok()?.as_string()
+}
+
+// A human readable version of an arbitrary value, ex. an abort reason.
+fn describe(value: &JsValue) -> String {
+	if let Some(s) = value.as_string() {
+		return s;
+	}
+
+	if let Some(message) = property(value, "message") {
+		return message;
+	}
+
+	format!("{value:?}")
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod test {
+	use super::*;
+
+	use wasm_bindgen_test::wasm_bindgen_test;
+
+	fn named(name: &str, message: &str) -> JsValue {
+		let err = js_sys::Error::new(message);
+		err.set_name(name);
+		err.into()
+	}
+
+	#[wasm_bindgen_test]
+	fn type_error() {
+		let err = Error::from(JsValue::from(js_sys::TypeError::new("bad chunk")));
+		assert!(matches!(&err, Error::TypeError { message, stack: Some(_) } if message == "bad chunk"));
+		assert_eq!(io::Error::from(err.clone()).kind(), io::ErrorKind::InvalidInput);
+
+		let value = JsValue::from(err);
+		assert!(value.is_instance_of::<js_sys::TypeError>());
+	}
+
+	#[wasm_bindgen_test]
+	fn abort_error() {
+		// The reason is kept as-is, so it round trips to the same object.
+		let reason = named("AbortError", "stopped");
+		let err = Error::from(reason.clone());
+		assert!(matches!(&err, Error::Aborted(_)));
+		assert!(js_sys::Object::is(&JsValue::from(err), &reason));
+	}
+
+	#[wasm_bindgen_test]
+	fn other() {
+		let err = Error::from(named("NetworkError", "reset"));
+		assert!(
+			matches!(&err, Error::Other { name, message, stack: Some(_) } if name == "NetworkError" && message == "reset")
+		);
+		assert_eq!(io::Error::from(err.clone()).kind(), io::ErrorKind::ConnectionReset);
+
+		// The name survives the round trip back into JS.
+		let value: js_sys::Error = JsValue::from(err).unchecked_into();
+		assert_eq!(value.name(), "NetworkError");
+		assert_eq!(value.message(), "reset");
+	}
+
+	#[wasm_bindgen_test]
+	fn not_an_error() {
+		let err = Error::from(JsValue::from_str("oops"));
+		assert!(matches!(&err, Error::Other { name, message, stack: None } if name == "Error" && message == "oops"));
 	}
 }
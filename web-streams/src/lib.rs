@@ -1,9 +1,27 @@
+mod byte_reader;
 mod error;
+mod pipe;
 mod promise;
 mod reader;
+mod sink;
+mod source;
+mod strategy;
+mod tee;
+mod transform;
 mod writer;
 
+pub use byte_reader::*;
 pub use error::*;
+pub use pipe::*;
 pub(crate) use promise::*;
 pub use reader::*;
+pub use sink::*;
+pub use source::*;
+pub use strategy::*;
+pub use tee::*;
+pub use transform::*;
 pub use writer::*;
+
+// Some tests use browser-only APIs, ex. VideoFrame.
+#[cfg(all(test, target_arch = "wasm32"))]
+wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
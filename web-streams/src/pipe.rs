@@ -0,0 +1,192 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+	js_sys::{Array, Promise},
+	AbortController, AbortSignal, ReadableStream, ReadableStreamDefaultReader, ReadableWritablePair, StreamPipeOptions,
+	WritableStream,
+};
+
+use crate::Error;
+
+/// Options for [pipe_to] and [pipe_through].
+#[derive(Debug, Clone, Default)]
+pub struct PipeOptions {
+	/// Don't close the destination when the source closes.
+	pub prevent_close: bool,
+
+	/// Don't abort the destination when the source errors.
+	pub prevent_abort: bool,
+
+	/// Don't cancel the source when the destination errors.
+	pub prevent_cancel: bool,
+
+	/// Stop the pipe early, see [PipeCancel].
+	pub cancel: Option<PipeCancel>,
+}
+
+impl PipeOptions {
+	fn to_js(&self) -> StreamPipeOptions {
+		let options = StreamPipeOptions::new();
+		options.set_prevent_close(self.prevent_close);
+		options.set_prevent_abort(self.prevent_abort);
+		options.set_prevent_cancel(self.prevent_cancel);
+
+		if let Some(cancel) = &self.cancel {
+			options.set_signal(&cancel.signal());
+		}
+
+		options
+	}
+}
+
+/// A handle used to stop a pipe, backed by an [AbortController].
+///
+/// Unless prevented by [PipeOptions], canceling also cancels the source and aborts the destination.
+#[derive(Debug, Clone)]
+pub struct PipeCancel {
+	controller: AbortController,
+}
+
+impl PipeCancel {
+	pub fn new() -> Result<Self, Error> {
+		Ok(Self {
+			controller: AbortController::new()?,
+		})
+	}
+
+	/// Stop the pipe with the given reason.
+	pub fn cancel(&self, reason: &str) {
+		self.controller.abort_with_reason(&JsValue::from_str(reason));
+	}
+
+	pub fn is_canceled(&self) -> bool {
+		self.signal().aborted()
+	}
+
+	/// The signal passed to `pipeTo`, which can also be shared with other APIs like `fetch`.
+	pub fn signal(&self) -> AbortSignal {
+		self.controller.signal()
+	}
+}
+
+/// The reason a pipe failed.
+#[derive(Debug, thiserror::Error, Clone)]
+pub enum PipeError {
+	#[error("source error: {0}")]
+	Source(Error),
+
+	#[error("destination error: {0}")]
+	Destination(Error),
+
+	#[error("canceled: {0}")]
+	Canceled(Error),
+}
+
+/// Pipe every chunk from the source to the destination, resolving once the destination is closed.
+///
+/// Both streams are locked until the pipe finishes. Dropping the future does not stop the pipe; use
+/// [PipeOptions::cancel] instead.
+pub async fn pipe_to(
+	source: &ReadableStream,
+	destination: &WritableStream,
+	options: PipeOptions,
+) -> Result<(), PipeError> {
+	let promise = source.pipe_to_with_options(destination, &options.to_js());
+	let Err(err) = JsFuture::from(promise).await else {
+		return Ok(());
+	};
+
+	if let Some(cancel) = options.cancel.filter(PipeCancel::is_canceled) {
+		return Err(PipeError::Canceled(Error::Canceled(cancel.signal().reason())));
+	}
+
+	// The locks are released by now, so check if the source errored; otherwise it was the destination.
+	match errored(source).await {
+		Some(reason) => Err(PipeError::Source(reason.into())),
+		None => Err(PipeError::Destination(err.into())),
+	}
+}
+
+/// Pipe the source through a transform, ex. [crate::Transform::pair], returning its readable side.
+///
+/// Any error is reported by the returned stream instead.
+pub fn pipe_through(source: &ReadableStream, transform: &ReadableWritablePair, options: PipeOptions) -> ReadableStream {
+	source.pipe_through_with_options(transform, &options.to_js())
+}
+
+// Returns the error if the stream has errored, without waiting.
+async fn errored(stream: &ReadableStream) -> Option<JsValue> {
+	if stream.locked() {
+		return None;
+	}
+
+	let reader: ReadableStreamDefaultReader = stream.get_reader().unchecked_into();
+
+	// An errored stream has an already rejected `closed` promise, which settles before the resolved one.
+	let race = Promise::race(&Array::of2(&reader.closed(), &Promise::resolve(&JsValue::UNDEFINED)));
+	let res = JsFuture::from(race).await;
+
+	reader.release_lock();
+	res.err()
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod test {
+	use super::*;
+
+	use futures::stream;
+	use wasm_bindgen_test::wasm_bindgen_test;
+	use web_sys::{js_sys, ReadableStreamDefaultController, UnderlyingSource};
+
+	use crate::{readable_from_stream, sleep, writable_from_fn, QueuingStrategy};
+
+	fn discard() -> WritableStream {
+		writable_from_fn(|_: JsValue| async { Ok::<_, Error>(()) }, &QueuingStrategy::default()).unwrap()
+	}
+
+	#[wasm_bindgen_test]
+	async fn source_error() {
+		let start = Closure::once_into_js(|controller: ReadableStreamDefaultController| {
+			controller.error_with_e(&js_sys::Error::new("source"));
+		});
+
+		let init = UnderlyingSource::new();
+		init.set_start(start.unchecked_ref());
+		let source = ReadableStream::new_with_underlying_source(&init).unwrap();
+
+		let err = pipe_to(&source, &discard(), PipeOptions::default()).await.unwrap_err();
+		assert!(matches!(err, PipeError::Source(Error::Other { message, .. }) if message == "source"));
+	}
+
+	#[wasm_bindgen_test]
+	async fn destination_error() {
+		let source = readable_from_stream(stream::iter(["a"]), &QueuingStrategy::default()).unwrap();
+		let destination = writable_from_fn(|_: JsValue| async { Err("destination") }, &QueuingStrategy::default());
+
+		let err = pipe_to(&source, &destination.unwrap(), PipeOptions::default())
+			.await
+			.unwrap_err();
+		assert!(matches!(err, PipeError::Destination(Error::Other { message, .. }) if message == "destination"));
+	}
+
+	#[wasm_bindgen_test]
+	async fn canceled() {
+		let source = readable_from_stream(stream::pending::<JsValue>(), &QueuingStrategy::default()).unwrap();
+		let destination = discard();
+		let cancel = PipeCancel::new().unwrap();
+
+		let options = PipeOptions {
+			cancel: Some(cancel.clone()),
+			..Default::default()
+		};
+
+		// Cancel while the pipe is waiting on the source.
+		let (res, ()) = futures::join!(pipe_to(&source, &destination, options), async {
+			sleep(10).await;
+			cancel.cancel("stop");
+		});
+
+		let err = res.unwrap_err();
+		assert!(matches!(err, PipeError::Canceled(Error::Canceled(reason)) if reason == "stop"));
+	}
+}
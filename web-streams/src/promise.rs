@@ -14,3 +14,16 @@ impl PromiseExt for js_sys::Promise {
 		closure.forget();
 	}
 }
+
+// Wait for the given number of milliseconds, so tests can let the stream machinery settle.
+#[cfg(all(test, target_arch = "wasm32"))]
+pub(crate) async fn sleep(ms: i32) {
+	#[wasm_bindgen]
+	extern "C" {
+		#[wasm_bindgen(js_name = setTimeout)]
+		fn set_timeout(handler: &js_sys::Function, ms: i32);
+	}
+
+	let promise = js_sys::Promise::new(&mut |resolve, _| set_timeout(&resolve, ms));
+	wasm_bindgen_futures::JsFuture::from(promise).await.unwrap();
+}
@@ -1,9 +1,14 @@
-use std::marker::PhantomData;
+use std::{
+	future::{poll_fn, Future},
+	marker::PhantomData,
+	pin::Pin,
+	task::{ready, Context, Poll},
+};
 
-use js_sys::Reflect;
+use futures_core::{FusedStream, Stream};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{js_sys, ReadableStream, ReadableStreamDefaultReader, ReadableStreamReadResult};
+use web_sys::{ReadableStream, ReadableStreamDefaultReader, ReadableStreamReadResult};
 
 use crate::{Error, PromiseExt};
 
@@ -14,44 +19,78 @@ pub struct Reader<T: JsCast> {
 	// Keep the most recent promise to make `read` cancelable
 	read: Option<JsFuture>,
 
-	_phantom: PhantomData<T>,
+	// The unconsumed bytes of the last chunk read by AsyncRead or AsyncBufRead, returned first by poll_read.
+	#[cfg(feature = "tokio")]
+	buffer: tokio_impl::Buffer,
+
+	// Reader is Unpin regardless of T.
+	_phantom: PhantomData<fn() -> T>,
 }
 
 impl<T: JsCast> Reader<T> {
 	/// Grab a lock on the given readable stream until dropped.
 	pub fn new(stream: &ReadableStream) -> Result<Self, Error> {
+		if stream.locked() {
+			return Err(Error::Locked);
+		}
+
 		let inner = stream.get_reader().unchecked_into();
 		Ok(Self {
 			inner,
 			read: None,
+			#[cfg(feature = "tokio")]
+			buffer: Default::default(),
 			_phantom: PhantomData,
 		})
 	}
 
 	/// Read the next element from the stream, returning None if the stream is done.
+	///
+	/// This is cancel-safe: if the future is dropped, the pending read is resumed by the next call.
 	pub async fn read(&mut self) -> Result<Option<T>, Error> {
-		if self.read.is_none() {
-			self.read = Some(JsFuture::from(self.inner.read()));
+		poll_fn(|cx| self.poll_read(cx)).await
+	}
+
+	/// Poll for the next element, returning None if the stream is done.
+	///
+	/// Any bytes left over from a partial [tokio::io::AsyncRead] or [tokio::io::AsyncBufRead] are returned first.
+	pub fn poll_read(&mut self, cx: &mut Context<'_>) -> Poll<Result<Option<T>, Error>> {
+		// Only a Reader<Uint8Array> ever fills the buffer.
+		#[cfg(feature = "tokio")]
+		if let Some(chunk) = self.buffer.take() {
+			return Poll::Ready(Ok(Some(chunk.unchecked_into())));
 		}
 
-		let result: ReadableStreamReadResult = self.read.as_mut().unwrap().await?.into();
-		self.read.take(); // Clear the promise on success
+		let value = ready!(self.poll_value(cx))?;
+		Poll::Ready(Ok(value.map(JsCast::unchecked_into)))
+	}
+
+	// Poll the underlying reader for the next value, without checking its type.
+	fn poll_value(&mut self, cx: &mut Context<'_>) -> Poll<Result<Option<JsValue>, Error>> {
+		let read = self.read.get_or_insert_with(|| JsFuture::from(self.inner.read()));
+		let result = ready!(Pin::new(read).poll(cx));
+		self.read.take(); // Clear the promise once resolved
 
-		//todo why do you use `Reflect` here?
-		// is get_done and get_value not good enough?
-		if Reflect::get(&result, &"done".into())?.is_truthy() {
-			return Ok(None);
+		let result: ReadableStreamReadResult = result?.unchecked_into();
+		if result.get_done().unwrap_or(false) {
+			return Poll::Ready(Ok(None));
 		}
 
-		let res = Reflect::get(&result, &"value".into())?.unchecked_into();
+		Poll::Ready(Ok(Some(result.get_value())))
+	}
 
-		Ok(Some(res))
+	/// Convert into a [Stream] that ends after the first error, releasing the lock once finished.
+	pub fn into_stream(self) -> ReaderStream<T> {
+		ReaderStream { reader: Some(self) }
 	}
 
 	/// Abort the stream early with the given reason.
 	pub fn abort(&mut self, reason: &str) {
-		let str = JsValue::from_str(reason);
-		self.inner.cancel_with_reason(&str).ignore();
+		self.cancel(&JsValue::from_str(reason));
+	}
+
+	pub(crate) fn cancel(&mut self, reason: &JsValue) {
+		self.inner.cancel_with_reason(reason).ignore();
 	}
 
 	pub async fn closed(&self) -> Result<(), Error> {
@@ -67,91 +106,226 @@ impl<T: JsCast> Drop for Reader<T> {
 	}
 }
 
+impl<T: JsCast> Stream for Reader<T> {
+	type Item = Result<T, Error>;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		self.get_mut().poll_read(cx).map(Result::transpose)
+	}
+}
+
+/// A [Reader] as a fused [Stream], returned by [Reader::into_stream].
+pub struct ReaderStream<T: JsCast> {
+	// Dropped once the stream is done or errored.
+	reader: Option<Reader<T>>,
+}
+
+impl<T: JsCast> Stream for ReaderStream<T> {
+	type Item = Result<T, Error>;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		let this = self.get_mut();
+		let Some(reader) = this.reader.as_mut() else {
+			return Poll::Ready(None);
+		};
+
+		let res = ready!(reader.poll_read(cx));
+		if !matches!(res, Ok(Some(_))) {
+			this.reader = None;
+		}
+
+		Poll::Ready(res.transpose())
+	}
+}
+
+impl<T: JsCast> FusedStream for ReaderStream<T> {
+	fn is_terminated(&self) -> bool {
+		self.reader.is_none()
+	}
+}
+
+impl<T: JsCast> From<Reader<T>> for ReaderStream<T> {
+	fn from(reader: Reader<T>) -> Self {
+		reader.into_stream()
+	}
+}
 
 use wasm_bindgen::JsCast;
 
 #[cfg(feature = "tokio")]
 mod tokio_impl {
-	use std::io::{Result, Error, ErrorKind, ErrorKind::Unsupported};
 	use super::*;
-	use std::pin::Pin;
-	use std::task::{Context, Poll};
-	use tokio::io::{AsyncRead, ReadBuf};
-	use wasm_bindgen::JsCast;
-	use crate::reader::js_sys::Uint8Array;
-	use std::future::Future;
-	use Poll::{Pending, Ready};
-	use js_sys::Promise;
-	use ErrorKind::Other;
-	use tracing::info;
 
-	impl AsyncRead for Reader<Uint8Array> {
+	use std::io;
+	use tokio::io::{AsyncBufRead, AsyncRead, ReadBuf};
+	use web_sys::js_sys::Uint8Array;
 
-		fn poll_read(
-			mut self: Pin<&mut Self>,
-			cx: &mut Context<'_>,
-			buf: &mut ReadBuf<'_>,
-		) -> Poll<Result<()>> {
+	// A chunk is copied into WASM memory once, and then handed out in pieces.
+	#[derive(Default)]
+	pub(super) struct Buffer {
+		data: Vec<u8>,
+		offset: usize,
+	}
 
-			//if there is no pending read, we need to create one
-			if self.read.is_none() {
-				self.read = Some(JsFuture::from(self.inner.read()));
+	impl Buffer {
+		// Replace the contents with the chunk, reusing the allocation.
+		fn fill(&mut self, chunk: &Uint8Array) {
+			self.data.resize(chunk.length() as usize, 0);
+			chunk.copy_to(&mut self.data);
+			self.offset = 0;
+		}
+
+		fn remaining(&self) -> &[u8] {
+			&self.data[self.offset..]
+		}
+
+		// Copy any unconsumed bytes back into a JS chunk, emptying the buffer.
+		pub(super) fn take(&mut self) -> Option<Uint8Array> {
+			let remaining = self.remaining();
+			if remaining.is_empty() {
+				return None;
 			}
 
-			let Some(promise) =  self.read.as_mut() else {
-				return Ready(Err(Error::new(Other, "Unrecoverable error: No pending read found despite just queued")));
-			};
-
-			match Pin::new(promise).poll(cx) {
-				Pending => Pending,
-				Ready(Ok(js_val)) => {
-					//we clone, set and then take here because
-					//in case of pending it needs to be in read
-					self.read.take();
-
-					//it seems at the moment that the value cannot be typechecked?
-					let result = js_val.unchecked_into::<ReadableStreamReadResult>();
-					/*let Ok(result) = js_val.dyn_into::<ReadableStreamReadResult>() else {
-						return Ready(Err(Error::new(Unsupported, "Unrecoverable error: Expected js type ReadableStreamReadResult")));
-					};*/
-					if result.get_done().unwrap_or(false) {
-						return Ready(Ok(())); // EOF
-					}
-
-					let Ok(array) = result.get_value().dyn_into::<Uint8Array>() else {
-						return Ready(Err(Error::new(Unsupported, "Unrecoverable error: Expected js type Uint8Array")));
-					};
-					let array_len = array.length() as usize;
-					let len = std::cmp::min(buf.remaining(), array_len);
-
-					// Copy what fits
-					// # Safety: copy_to_uninit does not uninit anything and inits the first `len` bytes.
-					let dst = unsafe {
-						&mut buf.unfilled_mut()[0..len]
-					};
-					array.slice(0, len as u32).copy_to_uninit(dst);
-					unsafe { buf.assume_init(len); }
-					buf.advance(len);
-
-					// If there are leftover bytes, we must not drop them
-					// create a new ReadableStreamReadResult and set self.read
-					if len < array_len {
-						let leftover = array.slice(len as u32, array_len as u32);
-						//let result = ReadableStreamReadResult::new(); i believe we can reuse the existing one
-						result.set_done(false);
-						result.set_value(&**leftover);
-						let promise = Promise::resolve(&**result);
-						self.read = Some(JsFuture::from(promise));
-					}
-
-					Ready(Ok(()))
+			let chunk = Uint8Array::from(remaining);
+			self.offset = self.data.len();
+			Some(chunk)
+		}
+	}
+
+	impl Reader<Uint8Array> {
+		// Returns the next non-empty chunk, or None if the stream is done.
+		// Only called once the buffer is empty, so the chunk always comes from the stream.
+		fn poll_chunk(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<Option<Uint8Array>>> {
+			loop {
+				let Some(value) = ready!(self.poll_value(cx))? else {
+					return Poll::Ready(Ok(None));
+				};
+
+				let chunk: Uint8Array = value
+					.dyn_into()
+					.map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "expected a Uint8Array chunk"))?;
+
+				if chunk.length() > 0 {
+					return Poll::Ready(Ok(Some(chunk)));
 				}
-				Ready(Err(_)) => {
-					self.read.take();
-					Ready(Err(Error::new(Other, "js read error")))
+			}
+		}
+	}
+
+	/// Chunks that fit are copied directly into the caller's buffer; otherwise the leftover is buffered in Rust.
+	impl AsyncRead for Reader<Uint8Array> {
+		fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+			let this = self.get_mut();
+
+			if this.buffer.remaining().is_empty() {
+				let Some(chunk) = ready!(this.poll_chunk(cx))? else {
+					return Poll::Ready(Ok(())); // EOF
+				};
+
+				let size = chunk.length() as usize;
+				if size <= buf.remaining() {
+					// SAFETY: copy_to_uninit initializes every byte of the slice.
+					let dst = unsafe { &mut buf.unfilled_mut()[..size] };
+					chunk.copy_to_uninit(dst);
+					unsafe { buf.assume_init(size) };
+					buf.advance(size);
+
+					return Poll::Ready(Ok(()));
 				}
+
+				this.buffer.fill(&chunk);
 			}
+
+			let remaining = this.buffer.remaining();
+			let size = remaining.len().min(buf.remaining());
+			buf.put_slice(&remaining[..size]);
+			this.buffer.offset += size;
+
+			Poll::Ready(Ok(()))
+		}
+	}
+
+	impl AsyncBufRead for Reader<Uint8Array> {
+		fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+			let this = self.get_mut();
+
+			if this.buffer.remaining().is_empty() {
+				let Some(chunk) = ready!(this.poll_chunk(cx))? else {
+					return Poll::Ready(Ok(&[])); // EOF
+				};
+
+				this.buffer.fill(&chunk);
+			}
+
+			Poll::Ready(Ok(this.buffer.remaining()))
+		}
+
+		fn consume(self: Pin<&mut Self>, amt: usize) {
+			let buffer = &mut self.get_mut().buffer;
+			buffer.offset = (buffer.offset + amt).min(buffer.data.len());
 		}
 	}
 }
 
+#[cfg(all(test, target_arch = "wasm32"))]
+mod test {
+	use super::*;
+
+	use futures::{stream, StreamExt};
+	use wasm_bindgen_test::wasm_bindgen_test;
+
+	use crate::{readable_from_stream, QueuingStrategy};
+
+	#[wasm_bindgen_test]
+	async fn into_stream() {
+		let stream = readable_from_stream(stream::iter(["a", "b", "c"]), &QueuingStrategy::default()).unwrap();
+		let mut chunks = Reader::<JsValue>::new(&stream).unwrap().into_stream();
+
+		let values: Vec<_> = (&mut chunks)
+			.map(|chunk| chunk.unwrap().as_string().unwrap())
+			.collect()
+			.await;
+		assert_eq!(values, ["a", "b", "c"]);
+
+		// Fused once done, releasing the lock.
+		assert!(chunks.is_terminated());
+		assert!(chunks.next().await.is_none());
+		assert!(!stream.locked());
+	}
+
+	#[cfg(feature = "tokio")]
+	#[wasm_bindgen_test]
+	async fn read_line() {
+		use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+		use web_sys::js_sys::Uint8Array;
+
+		let chunks = ["hel", "lo\nwor", "", "ld\nrest"].map(|chunk| Uint8Array::from(chunk.as_bytes()));
+		let stream = readable_from_stream(stream::iter(chunks), &QueuingStrategy::default()).unwrap();
+		let mut reader = Reader::<Uint8Array>::new(&stream).unwrap();
+
+		let mut line = String::new();
+		reader.read_line(&mut line).await.unwrap();
+		assert_eq!(line, "hello\n");
+
+		line.clear();
+		reader.read_line(&mut line).await.unwrap();
+		assert_eq!(line, "world\n");
+
+		let mut rest = String::new();
+		reader.read_to_string(&mut rest).await.unwrap();
+		assert_eq!(rest, "rest");
+	}
+
+	#[cfg(feature = "tokio")]
+	#[wasm_bindgen_test]
+	async fn read_invalid_chunk() {
+		use tokio::io::AsyncReadExt;
+		use web_sys::js_sys::Uint8Array;
+
+		let stream = readable_from_stream(stream::iter(["text"]), &QueuingStrategy::default()).unwrap();
+		let mut reader = Reader::<Uint8Array>::new(&stream).unwrap();
+
+		let err = AsyncReadExt::read(&mut reader, &mut [0; 4]).await.unwrap_err();
+		assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+	}
+}
@@ -0,0 +1,249 @@
+use std::{
+	any::Any,
+	cell::Cell,
+	fmt,
+	future::{poll_fn, Future},
+	pin::Pin,
+	rc::Rc,
+};
+
+use futures_sink::Sink;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::future_to_promise;
+use web_sys::{
+	js_sys::{self, Function, Promise},
+	UnderlyingSink, WritableStream,
+};
+
+use crate::{Error, QueuingStrategy};
+
+/// Create a [WritableStream] that forwards each chunk to a Rust [Sink].
+///
+/// The stream doesn't start until [Sink::poll_ready] first resolves, so a sink that fails to open errors the stream
+/// before the first write.
+/// Each write waits for [Sink::poll_ready], calls [Sink::start_send], then waits for [Sink::poll_flush], so a
+/// buffering sink's backpressure and errors propagate to the writer along with the `strategy` high-water mark.
+/// Closing the stream closes the sink, while aborting it drops the sink. A sink error errors the stream, and
+/// chunks that are not a `T` are rejected.
+///
+/// A sink error that is an [Error] is converted back into the original JS value; any other error becomes a JS
+/// `Error` with the same message.
+pub fn writable_from_sink<S, T>(sink: S, strategy: &QueuingStrategy) -> Result<WritableStream, Error>
+where
+	S: Sink<T> + 'static,
+	S::Error: fmt::Display + 'static,
+	T: JsCast,
+{
+	writable(SinkTarget(Box::pin(sink)), strategy)
+}
+
+/// Create a [WritableStream] that calls an async closure for each chunk.
+///
+/// The next chunk is not written until the previous future resolves. An error errors the stream, converted
+/// like [writable_from_sink].
+pub fn writable_from_fn<F, Fut, T, E>(f: F, strategy: &QueuingStrategy) -> Result<WritableStream, Error>
+where
+	F: FnMut(T) -> Fut + 'static,
+	Fut: Future<Output = Result<(), E>>,
+	E: fmt::Display + 'static,
+	T: JsCast,
+{
+	writable(FnTarget(f), strategy)
+}
+
+// The Rust side of an underlying sink.
+trait Target<T> {
+	async fn start(&mut self) -> Result<(), JsValue> {
+		Ok(())
+	}
+
+	async fn write(&mut self, chunk: T) -> Result<(), JsValue>;
+	async fn close(&mut self) -> Result<(), JsValue>;
+}
+
+// Keep the original JS value if the error came from JS, otherwise keep the message.
+fn to_js<E: fmt::Display + 'static>(err: E) -> JsValue {
+	match (&err as &dyn Any).downcast_ref::<Error>() {
+		Some(err) => err.clone().into(),
+		None => js_sys::Error::new(&err.to_string()).into(),
+	}
+}
+
+struct SinkTarget<S>(Pin<Box<S>>);
+
+impl<S: Sink<T>, T> Target<T> for SinkTarget<S>
+where
+	S::Error: fmt::Display + 'static,
+{
+	async fn start(&mut self) -> Result<(), JsValue> {
+		poll_fn(|cx| self.0.as_mut().poll_ready(cx)).await.map_err(to_js)
+	}
+
+	async fn write(&mut self, chunk: T) -> Result<(), JsValue> {
+		poll_fn(|cx| self.0.as_mut().poll_ready(cx)).await.map_err(to_js)?;
+		self.0.as_mut().start_send(chunk).map_err(to_js)?;
+
+		// Otherwise a buffered chunk would resolve the write before it's actually written.
+		poll_fn(|cx| self.0.as_mut().poll_flush(cx)).await.map_err(to_js)
+	}
+
+	async fn close(&mut self) -> Result<(), JsValue> {
+		poll_fn(|cx| self.0.as_mut().poll_close(cx)).await.map_err(to_js)
+	}
+}
+
+struct FnTarget<F>(F);
+
+impl<F, Fut, T, E> Target<T> for FnTarget<F>
+where
+	F: FnMut(T) -> Fut,
+	Fut: Future<Output = Result<(), E>>,
+	E: fmt::Display + 'static,
+{
+	async fn write(&mut self, chunk: T) -> Result<(), JsValue> {
+		(self.0)(chunk).await.map_err(to_js)
+	}
+
+	async fn close(&mut self) -> Result<(), JsValue> {
+		Ok(())
+	}
+}
+
+fn writable<U, T>(target: U, strategy: &QueuingStrategy) -> Result<WritableStream, Error>
+where
+	U: Target<T> + 'static,
+	T: JsCast,
+{
+	// The stream never calls the next callback until the previous promise resolves, except for abort.
+	// The target is taken while in use, and dropped once closed or aborted.
+	let target = Rc::new(Cell::new(Some(target)));
+
+	let start = {
+		let target = target.clone();
+		Closure::<dyn FnMut(JsValue) -> Promise>::new(move |_controller| {
+			let target = target.clone();
+			future_to_promise(async move {
+				let Some(mut inner) = target.take() else {
+					return Ok(JsValue::UNDEFINED);
+				};
+
+				let res = inner.start().await;
+				if res.is_ok() {
+					target.set(Some(inner));
+				}
+
+				res?;
+				Ok(JsValue::UNDEFINED)
+			})
+		})
+	};
+
+	let write = {
+		let target = target.clone();
+		Closure::<dyn FnMut(JsValue) -> Promise>::new(move |chunk: JsValue| {
+			let target = target.clone();
+			future_to_promise(async move {
+				let chunk = chunk
+					.dyn_into::<T>()
+					.map_err(|_| js_sys::TypeError::new("unexpected chunk type"))?;
+
+				let Some(mut inner) = target.take() else {
+					return Err(js_sys::Error::new("sink is closed").into());
+				};
+
+				let res = inner.write(chunk).await;
+				if res.is_ok() {
+					target.set(Some(inner));
+				}
+
+				res?;
+				Ok(JsValue::UNDEFINED)
+			})
+		})
+	};
+
+	let close = {
+		let target = target.clone();
+		Closure::<dyn FnMut() -> Promise>::new(move || {
+			let target = target.take();
+			future_to_promise(async move {
+				if let Some(mut inner) = target {
+					inner.close().await?;
+				}
+
+				Ok(JsValue::UNDEFINED)
+			})
+		})
+	};
+
+	let abort = Closure::<dyn FnMut(JsValue)>::new(move |_reason| drop(target.take()));
+
+	// The closures are owned by JS; only a few pointers remain after the target is dropped.
+	let init = UnderlyingSink::new();
+	init.set_start(start.into_js_value().unchecked_ref::<Function>());
+	init.set_write(write.into_js_value().unchecked_ref::<Function>());
+	init.set_close(close.into_js_value().unchecked_ref::<Function>());
+	init.set_abort(abort.into_js_value().unchecked_ref::<Function>());
+
+	let stream = WritableStream::new_with_underlying_sink_and_strategy(&init, &strategy.to_js()?)?;
+	Ok(stream)
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod test {
+	use super::*;
+
+	use std::task::{Context, Poll};
+	use wasm_bindgen_test::wasm_bindgen_test;
+
+	use crate::Writer;
+
+	// A sink that fails to open, ex. a connection that's refused.
+	struct Refused;
+
+	impl Sink<JsValue> for Refused {
+		type Error = Error;
+
+		fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+			Poll::Ready(Err(Error::Other {
+				name: "NetworkError".to_string(),
+				message: "refused".to_string(),
+				stack: None,
+			}))
+		}
+
+		fn start_send(self: Pin<&mut Self>, _item: JsValue) -> Result<(), Error> {
+			unreachable!("never ready")
+		}
+
+		fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+			Poll::Ready(Ok(()))
+		}
+
+		fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+			Poll::Ready(Ok(()))
+		}
+	}
+
+	#[wasm_bindgen_test]
+	async fn sink_error() {
+		let stream = writable_from_sink(Refused, &QueuingStrategy::default()).unwrap();
+		let mut writer = Writer::new(&stream).unwrap();
+
+		// The stream errors on start, before anything is written, keeping the original error name.
+		let err = writer.closed().await.unwrap_err();
+		assert!(matches!(&err, Error::Other { name, message, .. } if name == "NetworkError" && message == "refused"));
+		assert!(writer.write(&JsValue::from_str("a")).await.is_err());
+	}
+
+	#[wasm_bindgen_test]
+	async fn fn_error() {
+		let stream = writable_from_fn(|_: JsValue| async { Err("boom") }, &QueuingStrategy::default()).unwrap();
+		let mut writer = Writer::new(&stream).unwrap();
+
+		// Errors that aren't from JS keep their message.
+		let err = writer.write(&JsValue::from_str("a")).await.unwrap_err();
+		assert!(matches!(&err, Error::Other { name, message, .. } if name == "Error" && message == "boom"));
+		assert!(writer.closed().await.is_err());
+	}
+}
@@ -0,0 +1,187 @@
+use std::{cell::Cell, future::poll_fn, pin::Pin, rc::Rc};
+
+use futures_core::Stream;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::future_to_promise;
+use web_sys::{
+	js_sys::{Function, Promise, Uint8Array},
+	ReadableByteStreamController, ReadableStream, ReadableStreamDefaultController, ReadableStreamType,
+	UnderlyingSource,
+};
+
+use crate::{Error, QueuingStrategy};
+
+/// Create a [ReadableStream] that pulls each chunk from a Rust [Stream].
+///
+/// The stream is polled only while the queue has room according to `strategy`, and is dropped once it ends
+/// or the [ReadableStream] is canceled.
+pub fn readable_from_stream<S, T>(stream: S, strategy: &QueuingStrategy) -> Result<ReadableStream, Error>
+where
+	S: Stream<Item = T> + 'static,
+	T: Into<JsValue>,
+{
+	let source = Source::new(stream);
+
+	let pull = {
+		let source = source.clone();
+		move |controller: ReadableStreamDefaultController| {
+			let source = source.clone();
+			future_to_promise(async move {
+				loop {
+					let Some(chunk) = source.next().await else {
+						if !source.canceled.get() {
+							controller.close()?;
+						}
+						break;
+					};
+
+					controller.enqueue_with_chunk(&chunk.into())?;
+
+					if !controller.desired_size().is_some_and(|size| size > 0.0) {
+						break;
+					}
+				}
+
+				Ok(JsValue::UNDEFINED)
+			})
+		}
+	};
+
+	let pull = Closure::<dyn FnMut(ReadableStreamDefaultController) -> Promise>::new(pull);
+	let init = source.init(pull);
+
+	let stream = ReadableStream::new_with_underlying_source_and_strategy(&init, &strategy.to_js()?)?;
+	Ok(stream)
+}
+
+/// Create a byte [ReadableStream] that pulls each chunk from a Rust [Stream].
+///
+/// Like [readable_from_stream], but the result supports BYOB readers (see [crate::ByteReader]).
+/// Byte streams always measure the queue in bytes, so only the `strategy` high-water mark is used.
+/// A [QueuingStrategy::Custom] size function is rejected with [Error::TypeError].
+/// Each chunk is copied into JS and empty chunks are skipped.
+pub fn readable_bytes_from_stream<S, B>(stream: S, strategy: &QueuingStrategy) -> Result<ReadableStream, Error>
+where
+	S: Stream<Item = B> + 'static,
+	B: AsRef<[u8]>,
+{
+	let strategy = strategy.to_js_bytes()?;
+	let source = Source::new(stream);
+
+	let pull = {
+		let source = source.clone();
+		move |controller: ReadableByteStreamController| {
+			let source = source.clone();
+			future_to_promise(async move {
+				loop {
+					let Some(chunk) = source.next().await else {
+						if !source.canceled.get() {
+							controller.close()?;
+						}
+						break;
+					};
+
+					// Byte streams reject empty chunks.
+					let chunk = chunk.as_ref();
+					if chunk.is_empty() {
+						continue;
+					}
+
+					controller.enqueue_with_js_u8_array(&Uint8Array::from(chunk))?;
+
+					if !controller.desired_size().is_some_and(|size| size > 0.0) {
+						break;
+					}
+				}
+
+				Ok(JsValue::UNDEFINED)
+			})
+		}
+	};
+
+	let pull = Closure::<dyn FnMut(ReadableByteStreamController) -> Promise>::new(pull);
+	let init = source.init(pull);
+	init.set_type(ReadableStreamType::Bytes);
+
+	let stream = ReadableStream::new_with_underlying_source_and_strategy(&init, &strategy)?;
+	Ok(stream)
+}
+
+// The Rust side of an underlying source, shared by the pull and cancel callbacks.
+struct Source<S: Stream> {
+	// Taken while polling, and dropped once finished or canceled.
+	stream: Cell<Option<Pin<Box<S>>>>,
+	canceled: Cell<bool>,
+}
+
+impl<S: Stream + 'static> Source<S> {
+	fn new(stream: S) -> Rc<Self> {
+		Rc::new(Self {
+			stream: Cell::new(Some(Box::pin(stream))),
+			canceled: Cell::new(false),
+		})
+	}
+
+	// Returns the next item, or None if the stream has ended or was canceled.
+	async fn next(&self) -> Option<S::Item> {
+		let mut stream = self.stream.take()?;
+		let item = poll_fn(|cx| stream.as_mut().poll_next(cx)).await;
+
+		// Cancel may be called while polling, in which case the stream is dropped here instead.
+		if item.is_some() && !self.canceled.get() {
+			self.stream.set(Some(stream));
+		}
+
+		item
+	}
+
+	fn cancel(&self) {
+		self.canceled.set(true);
+		self.stream.take();
+	}
+
+	fn init<F: ?Sized + wasm_bindgen::closure::WasmClosure>(self: &Rc<Self>, pull: Closure<F>) -> UnderlyingSource {
+		let cancel = {
+			let source = self.clone();
+			Closure::<dyn FnMut(JsValue)>::new(move |_reason| source.cancel())
+		};
+
+		// The closures are owned by JS; only a few pointers remain after the stream is dropped.
+		let init = UnderlyingSource::new();
+		init.set_pull(pull.into_js_value().unchecked_ref::<Function>());
+		init.set_cancel(cancel.into_js_value().unchecked_ref::<Function>());
+
+		init
+	}
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod test {
+	use super::*;
+
+	use futures::{stream, StreamExt};
+	use wasm_bindgen_test::wasm_bindgen_test;
+
+	use crate::{sleep, Reader};
+
+	#[wasm_bindgen_test]
+	async fn backpressure() {
+		let polled = Rc::new(Cell::new(0));
+		let chunks = stream::iter(0..).inspect({
+			let polled = polled.clone();
+			move |_| polled.set(polled.get() + 1)
+		});
+
+		let stream = readable_from_stream(chunks.map(|i| i as f64), &QueuingStrategy::Count(2.0)).unwrap();
+
+		// The unbounded stream is only polled until the queue is full.
+		sleep(10).await;
+		assert_eq!(polled.get(), 2);
+
+		// Reading a chunk makes room for exactly one more.
+		let mut reader = Reader::<JsValue>::new(&stream).unwrap();
+		assert_eq!(reader.read().await.unwrap().unwrap().as_f64(), Some(0.0));
+		sleep(10).await;
+		assert_eq!(polled.get(), 3);
+	}
+}
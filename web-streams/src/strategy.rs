@@ -0,0 +1,128 @@
+use wasm_bindgen::prelude::*;
+use web_sys::{js_sys::Function, ByteLengthQueuingStrategy, QueuingStrategyInit};
+
+use crate::Error;
+
+/// How much a stream created by web-streams buffers before applying backpressure.
+///
+/// ```ignore
+/// // Buffer up to 1MB of encoded frames.
+/// let strategy = QueuingStrategy::custom(1_000_000.0, |chunk: &web_sys::EncodedVideoChunk| {
+///     chunk.byte_length() as f64
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub enum QueuingStrategy {
+	/// Buffer up to N chunks.
+	Count(f64),
+
+	/// Buffer up to N bytes, using the `byteLength` of each chunk.
+	ByteLength(f64),
+
+	/// Buffer until the total size reaches the high-water mark, created with [Self::custom].
+	Custom { high_water_mark: f64, size: Function },
+}
+
+impl QueuingStrategy {
+	/// Use a Rust function to compute the size of each chunk.
+	pub fn custom<T: JsCast, F: Fn(&T) -> f64 + 'static>(high_water_mark: f64, size: F) -> Self {
+		// A chunk of the wrong type has no size, which errors the stream instead of reinterpreting it.
+		let size = Closure::<dyn Fn(JsValue) -> f64>::new(move |chunk: JsValue| match chunk.dyn_ref() {
+			Some(chunk) => size(chunk),
+			None => f64::NAN,
+		});
+
+		// Owned by JS, since it's called for as long as the stream exists.
+		let size = size.into_js_value().unchecked_into();
+
+		Self::Custom { high_water_mark, size }
+	}
+
+	pub fn high_water_mark(&self) -> f64 {
+		match self {
+			Self::Count(n) | Self::ByteLength(n) => *n,
+			Self::Custom { high_water_mark, .. } => *high_water_mark,
+		}
+	}
+
+	pub(crate) fn to_js(&self) -> Result<web_sys::QueuingStrategy, Error> {
+		let strategy = match self {
+			// The default size function counts each chunk as 1.
+			Self::Count(n) => {
+				let strategy = web_sys::QueuingStrategy::new();
+				strategy.set_high_water_mark(*n);
+				strategy
+			}
+			// The native size function avoids calling into WASM for each chunk.
+			Self::ByteLength(n) => ByteLengthQueuingStrategy::new(&QueuingStrategyInit::new(*n))?.unchecked_into(),
+			Self::Custom { high_water_mark, size } => {
+				let strategy = web_sys::QueuingStrategy::new();
+				strategy.set_high_water_mark(*high_water_mark);
+				strategy.set_size(size);
+				strategy
+			}
+		};
+
+		Ok(strategy)
+	}
+
+	// Byte streams always measure chunks in bytes, and reject a size function.
+	pub(crate) fn to_js_bytes(&self) -> Result<web_sys::QueuingStrategy, Error> {
+		if let Self::Custom { .. } = self {
+			return Err(Error::TypeError {
+				message: "byte streams don't support a custom size function".to_string(),
+				stack: None,
+			});
+		}
+
+		let strategy = web_sys::QueuingStrategy::new();
+		strategy.set_high_water_mark(self.high_water_mark());
+		Ok(strategy)
+	}
+}
+
+impl Default for QueuingStrategy {
+	/// The default for most streams, buffering a single chunk.
+	fn default() -> Self {
+		Self::Count(1.0)
+	}
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod test {
+	use super::*;
+
+	use futures::{future, stream};
+	use wasm_bindgen_futures::JsFuture;
+	use wasm_bindgen_test::wasm_bindgen_test;
+	use web_sys::js_sys::Uint8Array;
+
+	use crate::{readable_bytes_from_stream, writable_from_fn};
+
+	#[wasm_bindgen_test]
+	async fn size() {
+		let strategy = QueuingStrategy::custom(10.0, |chunk: &Uint8Array| chunk.length() as f64);
+
+		// Never finish writing, so every chunk stays queued.
+		let stream = writable_from_fn(|_: JsValue| future::pending::<Result<(), Error>>(), &strategy).unwrap();
+		let writer = stream.get_writer().unwrap();
+
+		let _ = writer.write_with_chunk(&Uint8Array::new_with_length(4));
+		assert_eq!(writer.desired_size().unwrap(), Some(6.0));
+
+		// A chunk of the wrong type has no size, which errors the stream.
+		let _ = writer.write_with_chunk(&JsValue::from_str("abcd"));
+		let err: Error = JsFuture::from(writer.closed()).await.unwrap_err().into();
+		assert!(
+			matches!(&err, Error::Other { name, .. } if name == "RangeError"),
+			"{err}"
+		);
+	}
+
+	#[wasm_bindgen_test]
+	fn size_bytes() {
+		let strategy = QueuingStrategy::custom(10.0, |chunk: &Uint8Array| chunk.length() as f64);
+		let res = readable_bytes_from_stream(stream::empty::<Vec<u8>>(), &strategy);
+		assert!(matches!(res, Err(Error::TypeError { .. })));
+	}
+}
@@ -0,0 +1,301 @@
+use std::{
+	cell::RefCell,
+	collections::VecDeque,
+	future::poll_fn,
+	rc::Rc,
+	task::{ready, Context, Poll, Waker},
+};
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::future_to_promise;
+use web_sys::{
+	js_sys::{Function, Promise},
+	ReadableStream, ReadableStreamDefaultController, UnderlyingSource,
+};
+
+use crate::{Error, QueuingStrategy, Reader};
+
+/// Options for [tee] and [tee_streams].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TeeOptions {
+	/// The maximum number of chunks one branch can be ahead of the other.
+	///
+	/// The faster branch waits for the slower one once reached. The default is unbounded, like the native
+	/// `tee()`, which buffers every chunk the slower branch hasn't read yet.
+	pub max_divergence: Option<usize>,
+}
+
+/// Split the stream into two readers that each receive every chunk, locking the stream.
+///
+/// Unlike the native `tee()`, `VideoFrame` and `AudioData` chunks are cloned for the second branch, since each
+/// branch must close its own copy (ex. by dropping the web-codecs wrapper). Other chunks are shared between
+/// branches, so mutating one is visible to the other. If a chunk can't be cloned, ex. a closed frame, both branches
+/// error and the source is canceled.
+pub fn tee<T: JsCast>(stream: &ReadableStream, options: TeeOptions) -> Result<(Reader<T>, Reader<T>), Error> {
+	let (first, second) = tee_streams(stream, options)?;
+	Ok((Reader::new(&first)?, Reader::new(&second)?))
+}
+
+/// Like [tee], but returns the branches as streams, ex. to pass to JS or [crate::pipe_to].
+///
+/// The source is canceled once both branches are canceled.
+pub fn tee_streams(stream: &ReadableStream, options: TeeOptions) -> Result<(ReadableStream, ReadableStream), Error> {
+	let state = Rc::new(RefCell::new(Tee {
+		reader: Some(Reader::new(stream)?),
+		branches: Default::default(),
+		done: None,
+		max_divergence: options.max_divergence.map(|max| max.max(1)),
+	}));
+
+	Ok((branch(&state, 0)?, branch(&state, 1)?))
+}
+
+// The state shared by both branches.
+struct Tee {
+	// Dropped once the source is done, releasing the lock.
+	reader: Option<Reader<JsValue>>,
+	branches: [Branch; 2],
+
+	// The final result of the source, once it closes or errors.
+	done: Option<Result<(), Error>>,
+
+	max_divergence: Option<usize>,
+}
+
+#[derive(Default)]
+struct Branch {
+	// Chunks read from the source but not yet by this branch.
+	queue: VecDeque<JsValue>,
+	canceled: bool,
+
+	// Woken when there's a new chunk, or room to read one.
+	waker: Option<Waker>,
+}
+
+impl Tee {
+	// Returns the next chunk for the branch, reading from the source if needed.
+	fn poll_next(&mut self, index: usize, cx: &mut Context<'_>) -> Poll<Result<Option<JsValue>, Error>> {
+		let other = 1 - index;
+
+		if let Some(chunk) = self.branches[index].queue.pop_front() {
+			// The other branch may be waiting for room.
+			self.wake(other);
+			return Poll::Ready(Ok(Some(chunk)));
+		}
+
+		if let Some(done) = &self.done {
+			return Poll::Ready(done.clone().map(|_| None));
+		}
+
+		// Both branches may poll the same read, so register to be woken by the other branch too.
+		self.branches[index].waker = Some(cx.waker().clone());
+
+		let branch = &self.branches[other];
+		if !branch.canceled && self.max_divergence.is_some_and(|max| branch.queue.len() >= max) {
+			return Poll::Pending;
+		}
+
+		let res = ready!(self.reader.as_mut().unwrap().poll_read(cx));
+		self.branches[index].waker = None;
+		self.wake(other);
+
+		match res {
+			Ok(Some(chunk)) if self.branches[other].canceled => Poll::Ready(Ok(Some(chunk))),
+			Ok(Some(chunk)) => match clone_chunk(&chunk) {
+				Ok(copy) => {
+					self.branches[other].queue.push_back(copy);
+					Poll::Ready(Ok(Some(chunk)))
+				}
+				Err(err) => {
+					// Neither branch can skip the chunk, so error both and cancel the source.
+					close_chunk(&chunk);
+					if let Some(mut reader) = self.reader.take() {
+						reader.cancel(&err.clone().into());
+					}
+
+					self.finish(Err(err.clone()));
+					Poll::Ready(Err(err))
+				}
+			},
+			Ok(None) => {
+				self.finish(Ok(()));
+				Poll::Ready(Ok(None))
+			}
+			Err(err) => {
+				self.finish(Err(err.clone()));
+				Poll::Ready(Err(err))
+			}
+		}
+	}
+
+	fn cancel(&mut self, index: usize, reason: JsValue) {
+		let branch = &mut self.branches[index];
+		branch.canceled = true;
+
+		for chunk in branch.queue.drain(..) {
+			close_chunk(&chunk);
+		}
+
+		if self.branches.iter().all(|branch| branch.canceled) {
+			if let Some(mut reader) = self.reader.take() {
+				reader.cancel(&reason);
+			}
+		} else {
+			self.wake(1 - index);
+		}
+	}
+
+	fn finish(&mut self, res: Result<(), Error>) {
+		self.done = Some(res);
+		self.reader = None;
+	}
+
+	fn wake(&mut self, index: usize) {
+		if let Some(waker) = self.branches[index].waker.take() {
+			waker.wake();
+		}
+	}
+}
+
+impl Drop for Tee {
+	// A branch dropped without being canceled would otherwise leak the media chunks it never read.
+	fn drop(&mut self) {
+		for branch in &mut self.branches {
+			for chunk in branch.queue.drain(..) {
+				close_chunk(&chunk);
+			}
+		}
+	}
+}
+
+// Create the stream for one branch, with no JS-side buffering so the divergence is controlled by the Tee.
+fn branch(state: &Rc<RefCell<Tee>>, index: usize) -> Result<ReadableStream, Error> {
+	let pull = {
+		let state = state.clone();
+		Closure::<dyn FnMut(ReadableStreamDefaultController) -> Promise>::new(
+			move |controller: ReadableStreamDefaultController| {
+				let state = state.clone();
+				future_to_promise(async move {
+					match poll_fn(|cx| state.borrow_mut().poll_next(index, cx)).await {
+						Ok(Some(chunk)) => controller.enqueue_with_chunk(&chunk)?,
+						Ok(None) => controller.close()?,
+						Err(err) => controller.error_with_e(&err.into()),
+					}
+
+					Ok(JsValue::UNDEFINED)
+				})
+			},
+		)
+	};
+
+	let cancel = {
+		let state = state.clone();
+		Closure::<dyn FnMut(JsValue)>::new(move |reason| state.borrow_mut().cancel(index, reason))
+	};
+
+	// The closures are owned by JS; only the shared state remains after both branches are dropped.
+	let init = UnderlyingSource::new();
+	init.set_pull(pull.into_js_value().unchecked_ref::<Function>());
+	init.set_cancel(cancel.into_js_value().unchecked_ref::<Function>());
+
+	let strategy = QueuingStrategy::Count(0.0).to_js()?;
+	let stream = ReadableStream::new_with_underlying_source_and_strategy(&init, &strategy)?;
+	Ok(stream)
+}
+
+// Media chunks are closed by their owner, so each branch needs its own copy.
+fn clone_chunk(chunk: &JsValue) -> Result<JsValue, Error> {
+	if let Some(frame) = chunk.dyn_ref::<VideoFrame>() {
+		Ok(frame.clone()?)
+	} else if let Some(data) = chunk.dyn_ref::<AudioData>() {
+		Ok(data.clone()?)
+	} else {
+		Ok(chunk.clone())
+	}
+}
+
+fn close_chunk(chunk: &JsValue) {
+	if let Some(frame) = chunk.dyn_ref::<VideoFrame>() {
+		frame.close();
+	} else if let Some(data) = chunk.dyn_ref::<AudioData>() {
+		data.close();
+	}
+}
+
+// Avoids depending on the (unstable) WebCodecs features of web-sys.
+#[wasm_bindgen]
+extern "C" {
+	type VideoFrame;
+
+	#[wasm_bindgen(method, catch)]
+	fn clone(this: &VideoFrame) -> Result<JsValue, JsValue>;
+
+	#[wasm_bindgen(method)]
+	fn close(this: &VideoFrame);
+
+	type AudioData;
+
+	#[wasm_bindgen(method, catch)]
+	fn clone(this: &AudioData) -> Result<JsValue, JsValue>;
+
+	#[wasm_bindgen(method)]
+	fn close(this: &AudioData);
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod test {
+	use super::*;
+
+	use futures::{
+		future::{self, Either},
+		stream, StreamExt,
+	};
+	use wasm_bindgen_test::wasm_bindgen_test;
+	use web_sys::js_sys;
+
+	use crate::{readable_from_stream, sleep};
+
+	#[wasm_bindgen_test]
+	async fn divergence() {
+		let source = readable_from_stream(stream::iter(0..3).map(f64::from), &QueuingStrategy::default()).unwrap();
+		let options = TeeOptions {
+			max_divergence: Some(1),
+		};
+		let (mut first, mut second) = tee::<JsValue>(&source, options).unwrap();
+
+		assert_eq!(first.read().await.unwrap().unwrap(), 0.0);
+
+		// The first branch is a chunk ahead, so it waits for the second branch.
+		let read = first.read();
+		let Either::Left((_, read)) = future::select(Box::pin(sleep(10)), Box::pin(read)).await else {
+			panic!("read past the max divergence");
+		};
+		drop(read);
+
+		assert_eq!(second.read().await.unwrap().unwrap(), 0.0);
+		assert_eq!(first.read().await.unwrap().unwrap(), 1.0);
+		assert_eq!(second.read().await.unwrap().unwrap(), 1.0);
+	}
+
+	#[wasm_bindgen_test]
+	async fn clone_error() {
+		// A closed frame can't be cloned for the second branch.
+		let frame = js_sys::eval(
+			"const frame = new VideoFrame(new Uint8Array(16), \
+				{ format: 'RGBA', codedWidth: 2, codedHeight: 2, timestamp: 0 }); \
+			frame.close(); \
+			frame",
+		)
+		.unwrap();
+
+		let source = readable_from_stream(stream::iter([frame]), &QueuingStrategy::default()).unwrap();
+		let (mut first, mut second) = tee::<JsValue>(&source, TeeOptions::default()).unwrap();
+
+		let err = first.read().await.unwrap_err();
+		assert!(
+			matches!(&err, Error::Other { name, .. } if name == "InvalidStateError"),
+			"{err}"
+		);
+		assert!(second.read().await.is_err());
+	}
+}
@@ -0,0 +1,263 @@
+use std::{cell::Cell, fmt, future::Future, marker::PhantomData, rc::Rc};
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::future_to_promise;
+use web_sys::{
+	js_sys::{self, Function, Promise},
+	ReadableStream, ReadableWritablePair, TransformStream, TransformStreamDefaultController, Transformer as Init,
+	WritableStream,
+};
+
+use crate::{Error, QueuingStrategy, Reader, TypedWriter};
+
+/// Transforms each chunk written to a [Transform], see [Transform::new].
+pub trait Transformer<I, O> {
+	type Error: fmt::Display;
+
+	/// Called for each chunk written, enqueueing any number of output chunks.
+	///
+	/// The next chunk is not transformed until the returned future resolves.
+	fn transform(
+		&mut self,
+		chunk: I,
+		controller: &TransformController<O>,
+	) -> impl Future<Output = Result<(), Self::Error>>;
+
+	/// Called once the writable side is closed, to enqueue any remaining output.
+	fn flush(&mut self, controller: &TransformController<O>) -> impl Future<Output = Result<(), Self::Error>> {
+		let _ = controller;
+		async { Ok(()) }
+	}
+}
+
+/// Used by a [Transformer] to enqueue output chunks.
+pub struct TransformController<O> {
+	inner: TransformStreamDefaultController,
+	_phantom: PhantomData<fn(O)>,
+}
+
+impl<O: JsCast> TransformController<O> {
+	/// Enqueue a chunk on the readable side.
+	pub fn enqueue(&self, chunk: &O) -> Result<(), Error> {
+		self.inner.enqueue_with_chunk(chunk.as_ref())?;
+		Ok(())
+	}
+
+	/// The room left in the readable side's queue, or None if it errored.
+	pub fn desired_size(&self) -> Option<f64> {
+		self.inner.desired_size()
+	}
+
+	/// Close the readable side and error the writable side.
+	pub fn terminate(&self) {
+		self.inner.terminate();
+	}
+}
+
+/// A wrapper around a TransformStream driven by a Rust [Transformer].
+///
+/// JS writes to [Self::writable] and reads from [Self::readable], or uses [Self::pair] with `pipeThrough`.
+/// Rust can do the same via [Self::writer] and [Self::reader].
+pub struct Transform<I: JsCast, O: JsCast> {
+	inner: TransformStream,
+	_phantom: PhantomData<fn(I) -> O>,
+}
+
+impl<I: JsCast + 'static, O: JsCast + 'static> Transform<I, O> {
+	/// Create a TransformStream that calls the transformer for each chunk.
+	///
+	/// Chunks that are not an `I` error the stream, as does any error returned by the transformer.
+	pub fn new<T: Transformer<I, O> + 'static>(transformer: T) -> Result<Self, Error> {
+		// The defaults used by the TransformStream constructor.
+		Self::with_strategy(transformer, &QueuingStrategy::Count(1.0), &QueuingStrategy::Count(0.0))
+	}
+
+	/// Like [Self::new], but with custom strategies for the writable and readable sides.
+	pub fn with_strategy<T: Transformer<I, O> + 'static>(
+		transformer: T,
+		writable: &QueuingStrategy,
+		readable: &QueuingStrategy,
+	) -> Result<Self, Error> {
+		// The stream never calls the next callback until the previous promise resolves.
+		// The transformer is taken while in use, and dropped once flushed or errored.
+		let transformer = Rc::new(Cell::new(Some(transformer)));
+
+		let transform = {
+			let transformer = transformer.clone();
+			Closure::<dyn FnMut(JsValue, TransformStreamDefaultController) -> Promise>::new(
+				move |chunk: JsValue, controller| {
+					let transformer = transformer.clone();
+					let controller = TransformController {
+						inner: controller,
+						_phantom: PhantomData,
+					};
+
+					future_to_promise(async move {
+						let chunk = chunk
+							.dyn_into::<I>()
+							.map_err(|_| js_sys::TypeError::new("unexpected chunk type"))?;
+
+						let Some(mut inner) = transformer.take() else {
+							return Err(js_sys::Error::new("transform is closed").into());
+						};
+
+						let res = inner.transform(chunk, &controller).await;
+						if res.is_ok() {
+							transformer.set(Some(inner));
+						}
+
+						res.map_err(|err| js_sys::Error::new(&err.to_string()))?;
+						Ok(JsValue::UNDEFINED)
+					})
+				},
+			)
+		};
+
+		let flush = Closure::<dyn FnMut(TransformStreamDefaultController) -> Promise>::new(move |controller| {
+			let inner = transformer.take();
+			let controller = TransformController {
+				inner: controller,
+				_phantom: PhantomData,
+			};
+
+			future_to_promise(async move {
+				if let Some(mut inner) = inner {
+					inner
+						.flush(&controller)
+						.await
+						.map_err(|err| js_sys::Error::new(&err.to_string()))?;
+				}
+
+				Ok(JsValue::UNDEFINED)
+			})
+		});
+
+		// The closures are owned by JS; only a few pointers remain after the transformer is dropped.
+		let init = Init::new();
+		init.set_transform(transform.into_js_value().unchecked_ref::<Function>());
+		init.set_flush(flush.into_js_value().unchecked_ref::<Function>());
+
+		let inner = TransformStream::new_with_transformer_and_writable_strategy_and_readable_strategy(
+			&init,
+			&writable.to_js()?,
+			&readable.to_js()?,
+		)?;
+		Ok(Self {
+			inner,
+			_phantom: PhantomData,
+		})
+	}
+
+	/// Create a TransformStream that maps each chunk with an async closure.
+	pub fn from_fn<F, Fut, E>(f: F) -> Result<Self, Error>
+	where
+		F: FnMut(I) -> Fut + 'static,
+		Fut: Future<Output = Result<O, E>>,
+		E: fmt::Display,
+	{
+		Self::new(FnTransformer(f))
+	}
+
+	pub fn readable(&self) -> ReadableStream {
+		self.inner.readable()
+	}
+
+	pub fn writable(&self) -> WritableStream {
+		self.inner.writable()
+	}
+
+	/// Both sides, as used by [ReadableStream::pipe_through].
+	pub fn pair(&self) -> ReadableWritablePair {
+		ReadableWritablePair::new(&self.readable(), &self.writable())
+	}
+
+	/// Lock the readable side, to read the output from Rust.
+	pub fn reader(&self) -> Result<Reader<O>, Error> {
+		Reader::new(&self.readable())
+	}
+
+	/// Lock the writable side, to write the input from Rust.
+	pub fn writer(&self) -> Result<TypedWriter<I>, Error> {
+		TypedWriter::new(&self.writable())
+	}
+}
+
+impl<I: JsCast, O: JsCast> AsRef<TransformStream> for Transform<I, O> {
+	fn as_ref(&self) -> &TransformStream {
+		&self.inner
+	}
+}
+
+impl<I: JsCast, O: JsCast> From<Transform<I, O>> for TransformStream {
+	fn from(transform: Transform<I, O>) -> Self {
+		transform.inner
+	}
+}
+
+struct FnTransformer<F>(F);
+
+impl<I, O, F, Fut, E> Transformer<I, O> for FnTransformer<F>
+where
+	O: JsCast,
+	F: FnMut(I) -> Fut,
+	Fut: Future<Output = Result<O, E>>,
+	E: fmt::Display,
+{
+	type Error = String;
+
+	async fn transform(&mut self, chunk: I, controller: &TransformController<O>) -> Result<(), String> {
+		let output = (self.0)(chunk).await.map_err(|err| err.to_string())?;
+		controller.enqueue(&output).map_err(|err| err.to_string())
+	}
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod test {
+	use super::*;
+
+	use js_sys::JsString;
+	use wasm_bindgen_test::wasm_bindgen_test;
+
+	// Buffers every chunk and only outputs them once flushed.
+	#[derive(Default)]
+	struct Join(Vec<String>);
+
+	impl Transformer<JsString, JsString> for Join {
+		type Error = Error;
+
+		async fn transform(&mut self, chunk: JsString, _: &TransformController<JsString>) -> Result<(), Error> {
+			self.0.push(chunk.into());
+			Ok(())
+		}
+
+		async fn flush(&mut self, controller: &TransformController<JsString>) -> Result<(), Error> {
+			controller.enqueue(&self.0.join(",").into())
+		}
+	}
+
+	#[wasm_bindgen_test]
+	async fn flush() {
+		let transform = Transform::new(Join::default()).unwrap();
+		let mut writer = transform.writer().unwrap();
+		let mut reader = transform.reader().unwrap();
+
+		// The readable side has no buffer, so the writes wait for the reader.
+		let write = async {
+			for chunk in ["a", "b", "c"] {
+				writer.write(&chunk.into()).await.unwrap();
+			}
+			writer.close();
+		};
+
+		let read = async {
+			let mut output = Vec::new();
+			while let Some(chunk) = reader.read().await.unwrap() {
+				output.push(String::from(chunk));
+			}
+			output
+		};
+
+		let ((), output) = futures::join!(write, read);
+		assert_eq!(output, ["a,b,c"]);
+	}
+}
@@ -1,7 +1,13 @@
+use std::collections::VecDeque;
+use std::future::{poll_fn, Future};
 use std::marker::PhantomData;
 use std::mem::ManuallyDrop;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use futures_sink::Sink;
 use wasm_bindgen::prelude::*;
-use wasm_bindgen_futures::{js_sys, JsFuture};
+use wasm_bindgen_futures::JsFuture;
 use web_sys::{WritableStream, WritableStreamDefaultWriter};
 
 use crate::{Error, PromiseExt};
@@ -9,26 +15,38 @@ use crate::{Error, PromiseExt};
 // Wrapper around WritableStream
 pub struct Writer {
 	inner: WritableStreamDefaultWriter,
+	state: WriteState,
 }
 
 impl Writer {
 	pub fn new(stream: &WritableStream) -> Result<Self, Error> {
+		if stream.locked() {
+			return Err(Error::Locked);
+		}
+
 		let inner = stream.get_writer()?.unchecked_into();
-		Ok(Self { inner })
+		Ok(Self {
+			inner,
+			state: WriteState::default(),
+		})
 	}
 
+	/// Write a chunk and wait until it (and any earlier chunks) have been written.
 	pub async fn write(&mut self, v: &JsValue) -> Result<(), Error> {
-		JsFuture::from(self.inner.write_with_chunk(v)).await?;
-		Ok(())
+		self.state.start_send(&self.inner, v)?;
+		poll_fn(|cx| self.state.poll_writes(cx)).await
 	}
 
+	/// Close the stream once every queued chunk has been written; later writes fail with [Error::Closed].
 	pub fn close(&mut self) {
-		self.inner.close().ignore();
+		self.state.close(&self.inner);
 	}
 
+	/// Abort the stream, discarding any queued chunks; later writes fail with [Error::Aborted].
 	pub fn abort(&mut self, reason: &str) {
-		let str = JsValue::from_str(reason);
-		self.inner.abort_with_reason(&str).ignore();
+		let reason = JsValue::from_str(reason);
+		self.state.fail(Error::Aborted(reason.clone()));
+		self.inner.abort_with_reason(&reason).ignore();
 	}
 
 	pub async fn closed(&self) -> Result<(), Error> {
@@ -45,11 +63,11 @@ impl Drop for Writer {
 
 impl<T: JsCast> From<Writer> for TypedWriter<T> {
 	fn from(value: Writer) -> Self {
-		let value: ManuallyDrop<Writer> = ManuallyDrop::new(value);
+		let mut value: ManuallyDrop<Writer> = ManuallyDrop::new(value);
 
 		TypedWriter {
 			inner: value.inner.clone(),
-			write_promise: None,
+			state: std::mem::take(&mut value.state),
 			_phantom: PhantomData,
 		}
 	}
@@ -65,201 +83,343 @@ impl<T: JsCast> TryFrom<TypedWriter<T>> for Writer {
 	type Error = TypedWriter<T>;
 
 	fn try_from(value: TypedWriter<T>) -> Result<Self, Self::Error> {
-		if value.write_promise.is_some() {
+		if !value.state.writes.is_empty() {
 			Err(value)
 		} else {
-			let value: ManuallyDrop<TypedWriter<T>> = ManuallyDrop::new(value);
+			let mut value: ManuallyDrop<TypedWriter<T>> = ManuallyDrop::new(value);
 			Ok(Writer {
 				inner: value.inner.clone(),
+				state: std::mem::take(&mut value.state),
 			})
 		}
-
 	}
 }
 
-
 pub struct TypedWriter<T: JsCast> {
 	inner: WritableStreamDefaultWriter,
-	// Keep the most recent promise to make `write` cancelable
-	write_promise: Option<JsFuture>,
 
-	_phantom: PhantomData<T>,
+	// Keep the pending promises to make `write` cancelable
+	state: WriteState,
+
+	// TypedWriter is Unpin regardless of T.
+	_phantom: PhantomData<fn(T)>,
 }
 
 impl<T: JsCast> TypedWriter<T> {
-    pub fn new(stream: &WritableStream) -> Result<Self, Error> {
-        let inner = stream.get_writer()?.unchecked_into();
-        Ok(Self {
-            inner,
-            write_promise: None,
-            _phantom: PhantomData,
-        })
-    }
-
-    pub async fn write(&mut self, v: &T) -> Result<(), Error> {
-		if let Some(promise) = &mut  self.write_promise.take() {
-            promise.await?;
+	pub fn new(stream: &WritableStream) -> Result<Self, Error> {
+		if stream.locked() {
+			return Err(Error::Locked);
 		}
-        let js_value = JsValue::from(v);
-        self.write_promise = Some(JsFuture::from(self.inner.write_with_chunk(&js_value)));
-        if let Some(promise) = &mut self.write_promise {
-            promise.await?;
-            self.write_promise = None;
-        }
-        Ok(())
-    }
-
-    pub fn close(&mut self) {
-		/*if let Some(promise) = self.write_promise.take() {
-			promise.block;
-		}*/
-        self.inner.close().ignore();
-    }
-
-    pub fn abort(&mut self, reason: &str) {
-		/*if let Some(promise) = self.write_promise.take() {
-			promise.ignore();
-		}*/
-        let str = JsValue::from_str(reason);
-        self.inner.abort_with_reason(&str).ignore();
-    }
-
-    /// Wait for the stream to be closed
-    pub async fn closed(&self) -> Result<(), Error> {
-		//todo is it correct that this only requires &self?
-		/*if let Some(promise) = &self.write_promise {
-            promise.await?;
-		}*/
-
-        JsFuture::from(self.inner.closed()).await?;
-        Ok(())
-    }
+
+		let inner = stream.get_writer()?.unchecked_into();
+		Ok(Self {
+			inner,
+			state: WriteState::default(),
+			_phantom: PhantomData,
+		})
+	}
+
+	/// Write a chunk and wait until it (and any earlier chunks) have been written.
+	pub async fn write(&mut self, v: &T) -> Result<(), Error> {
+		self.state.start_send(&self.inner, v.as_ref())?;
+		poll_fn(|cx| self.state.poll_writes(cx)).await
+	}
+
+	/// Close the stream once every queued chunk has been written; later writes fail with [Error::Closed].
+	pub fn close(&mut self) {
+		self.state.close(&self.inner);
+	}
+
+	/// Abort the stream, discarding any queued chunks; later writes fail with [Error::Aborted].
+	pub fn abort(&mut self, reason: &str) {
+		let reason = JsValue::from_str(reason);
+		self.state.fail(Error::Aborted(reason.clone()));
+		self.inner.abort_with_reason(&reason).ignore();
+	}
+
+	/// Wait for the stream to be closed
+	pub async fn closed(&self) -> Result<(), Error> {
+		JsFuture::from(self.inner.closed()).await?;
+		Ok(())
+	}
+}
+
+// The pending promises shared by write, Sink, and AsyncWrite.
+#[derive(Default)]
+struct WriteState {
+	// Resolves when the stream no longer applies backpressure.
+	ready: Option<JsFuture>,
+
+	// Every write that hasn't resolved yet, in order.
+	writes: VecDeque<JsFuture>,
+
+	// Persisted across polls so the waker stays registered.
+	close: Option<JsFuture>,
+
+	// Set once closing, after which writes fail.
+	closed: bool,
+
+	// The first error, returned by every later call.
+	error: Option<Error>,
+}
+
+impl WriteState {
+	fn poll_ready(&mut self, inner: &WritableStreamDefaultWriter, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+		// Surface any errors from completed writes, and free them.
+		if let Poll::Ready(Err(err)) = self.poll_writes(cx) {
+			return Poll::Ready(Err(err));
+		}
+
+		if self.ready.is_none() {
+			// A null desiredSize means the stream errored, in which case `ready` rejects with the error.
+			if inner.desired_size()?.is_some_and(|size| size > 0.0) {
+				return Poll::Ready(Ok(()));
+			}
+
+			self.ready = Some(JsFuture::from(inner.ready()));
+		}
+
+		// Once resolved there's room for at least one more chunk.
+		let res = ready!(Pin::new(self.ready.as_mut().unwrap()).poll(cx));
+		self.ready = None;
+
+		Poll::Ready(res.map(|_| ()).map_err(|err| self.fail(err.into())))
+	}
+
+	fn start_send(&mut self, inner: &WritableStreamDefaultWriter, chunk: &JsValue) -> Result<(), Error> {
+		if let Some(err) = &self.error {
+			return Err(err.clone());
+		}
+
+		if self.closed {
+			return Err(Error::Closed);
+		}
+
+		self.writes.push_back(JsFuture::from(inner.write_with_chunk(chunk)));
+		Ok(())
+	}
+
+	// Wait for every write to resolve, returning the first error.
+	fn poll_writes(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+		if let Some(err) = &self.error {
+			return Poll::Ready(Err(err.clone()));
+		}
+
+		while let Some(write) = self.writes.front_mut() {
+			let res = ready!(Pin::new(write).poll(cx));
+			self.writes.pop_front();
+
+			if let Err(err) = res {
+				return Poll::Ready(Err(self.fail(err.into())));
+			}
+		}
+
+		Poll::Ready(Ok(()))
+	}
+
+	fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+		self.poll_writes(cx)
+	}
+
+	// Start closing without waiting; the stream can only be closed once, so poll_close reuses the promise.
+	fn close(&mut self, inner: &WritableStreamDefaultWriter) -> &mut JsFuture {
+		self.closed = true;
+		self.close.get_or_insert_with(|| JsFuture::from(inner.close()))
+	}
+
+	fn poll_close(&mut self, inner: &WritableStreamDefaultWriter, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+		self.closed = true;
+		ready!(self.poll_writes(cx))?;
+
+		// Resolves once every queued chunk has been written and the stream is closed.
+		let res = ready!(Pin::new(self.close(inner)).poll(cx));
+
+		Poll::Ready(res.map(|_| ()).map_err(|err| self.fail(err.into())))
+	}
+
+	fn fail(&mut self, err: Error) -> Error {
+		self.writes.clear();
+		self.error.get_or_insert(err).clone()
+	}
+}
+
+/// Writes are queued without waiting, subject to backpressure from [Sink::poll_ready].
+impl Sink<JsValue> for Writer {
+	type Error = Error;
+
+	fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+		let this = self.get_mut();
+		this.state.poll_ready(&this.inner, cx)
+	}
+
+	fn start_send(self: Pin<&mut Self>, item: JsValue) -> Result<(), Error> {
+		let this = self.get_mut();
+		this.state.start_send(&this.inner, &item)
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+		self.get_mut().state.poll_flush(cx)
+	}
+
+	fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+		let this = self.get_mut();
+		this.state.poll_close(&this.inner, cx)
+	}
+}
+
+/// Writes are queued without waiting, subject to backpressure from [Sink::poll_ready].
+impl<T: JsCast> Sink<T> for TypedWriter<T> {
+	type Error = Error;
+
+	fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+		let this = self.get_mut();
+		this.state.poll_ready(&this.inner, cx)
+	}
+
+	fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Error> {
+		let this = self.get_mut();
+		this.state.start_send(&this.inner, item.as_ref())
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+		self.get_mut().state.poll_flush(cx)
+	}
+
+	fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+		let this = self.get_mut();
+		this.state.poll_close(&this.inner, cx)
+	}
 }
 
 #[cfg(feature = "tokio")]
 mod tokio_impl {
-	use std::future::Future;
 	use super::*;
-    use std::io::{Result, Error, ErrorKind};
-    use std::pin::Pin;
-    use std::task::{Context, Poll};
-    use tokio::io::AsyncWrite;
-    use wasm_bindgen::JsCast;
-    use wasm_bindgen_futures::JsFuture;
-    use js_sys::Uint8Array;
-    use ErrorKind::{BrokenPipe, Other};
-    use std::task::Poll::Ready;
-    use Poll::Pending;
-    use tracing::info;
-
-    impl<T: JsCast + Unpin> TypedWriter<T> {
-		fn project(self: Pin<&mut Self>) -> (&mut WritableStreamDefaultWriter, &mut Option<JsFuture>) {
-			// Safety: None of the fields are self-referential or require pinning
+
+	use std::io;
+	use tokio::io::AsyncWrite;
+	use web_sys::js_sys::Uint8Array;
+
+	/// Each write is queued without waiting for it to complete, subject to backpressure from the stream.
+	/// An error from any write is returned by the next call.
+	impl AsyncWrite for TypedWriter<Uint8Array> {
+		fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+			if buf.is_empty() {
+				return Poll::Ready(Ok(0));
+			}
+
 			let this = self.get_mut();
-			(&mut this.inner, &mut this.write_promise)
+			ready!(this.state.poll_ready(&this.inner, cx))?;
+
+			// The data is copied into JS, so the buffer can be reused immediately.
+			this.state.start_send(&this.inner, &Uint8Array::from(buf))?;
+
+			Poll::Ready(Ok(buf.len()))
+		}
+
+		fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+			self.get_mut().state.poll_flush(cx).map_err(Into::into)
+		}
+
+		fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+			let this = self.get_mut();
+			this.state.poll_close(&this.inner, cx).map_err(Into::into)
 		}
 	}
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod test {
+	use super::*;
+
+	use std::{cell::RefCell, rc::Rc};
 
-    impl AsyncWrite for TypedWriter<Uint8Array> {
-        fn poll_write(
-            self: Pin<&mut Self>,
-            cx: &mut Context<'_>,
-            buf: &[u8],
-        ) -> Poll<Result<usize>> {
-            info!("poll_write called with buf{{len={}}}: {:?}", buf.len(), buf);
-            
-            let Ok(Some(desired_size)) = self.inner.desired_size() else {
-                return Ready(Err(Error::new(BrokenPipe, "stream is closed, not writable, or abort queued")));
-            };
-            
-            let (inner, write_promise) = Self::project(self);
-            info!("desired size: {}", desired_size);
-            if desired_size < 1f64 {
-                // if we return Pending here we must also ensure a waker is provided
-                return if let Some(promise) = write_promise {
-                    match Pin::new(promise).poll(cx) {
-                        Pending => Pending,
-                        Ready(Ok(_)) => {
-                            *write_promise = None;
-                            Ready(Ok(0))
-                        },
-                        Ready(Err(err)) => {
-                            *write_promise = None;
-                            let js_err_str = err.as_string().unwrap_or_else(|| "unknown error".to_string());
-                            Ready(Err(Error::new(Other, format!("js wait for write error: {}", js_err_str))))
-                        },
-                    }
-                } else {
-                    Ready(Ok(0)) // No pending write, nothing to flush
-                };
-
-                //return Ready(Err(Error::from(WouldBlock)));
-                //return Ready(Err(Error::new(WouldBlock, format!("desired size is too small: {}", desired_size))));
-            }
-            //let desired_size = desired_size as usize;
-            if let Some(promise) = write_promise {
-                if let Ready(Err(err)) = Pin::new(promise).poll(cx) {
-                    *write_promise = None;
-                    let js_err_str = err.as_string().unwrap_or_else(|| "unknown error".to_string());
-                    return Ready(Err(Error::new(Other, format!("js write error: {}", js_err_str))));
+	use futures::{stream, StreamExt};
+	use wasm_bindgen_test::wasm_bindgen_test;
+	use web_sys::js_sys::Uint8Array;
+
+	use crate::{readable_from_stream, writable_from_fn, QueuingStrategy, Reader};
+
+	#[wasm_bindgen_test]
+	async fn stream_to_sink() {
+		let written = Rc::new(RefCell::new(Vec::new()));
+		let destination = writable_from_fn(
+			{
+				let written = written.clone();
+				move |chunk: JsValue| {
+					written.borrow_mut().push(chunk.as_string().unwrap());
+					async { Ok::<_, Error>(()) }
 				}
-            }
-
-            //let len = std::cmp::min(buf.len(), desired_size);
-            let array = Uint8Array::from(buf);//.slice(0, len as u32);
-            //todo this looks like a proper issue to me!
-            let p = JsFuture::from(inner.write_with_chunk(&array));
-            *write_promise = Some(p); //this promise should only resolve after the current anyway
-            /*match write_promise {
-                Some(val) => {
-                    *val = val.then(&Closure::<dyn FnMut(JsValue)>::new(move |_| {
-                        p
-                    }));
-                },
-                opt @ None => *opt = Some(p),
-            }*/
-            Ready(Ok(buf.len()))
-        }
-
-        fn poll_flush(
-            self: Pin<&mut Self>,
-            cx: &mut Context<'_>,
-        ) -> Poll<Result<()>> {
-            let (_ , write_promise) = Self::project(self);
-            if let Some(promise) = write_promise {
-				match Pin::new(promise).poll(cx) {
-                    Pending => Pending,
-					Ready(Ok(_)) => {
-						*write_promise = None;
-                        Ready(Ok(()))
-					},
-					Ready(Err(err)) => {
-                        *write_promise = None;
-						let js_err_str = err.as_string().unwrap_or_else(|| "unknown error".to_string());
-						Ready(Err(Error::new(Other, format!("js flush error: {}", js_err_str))))
-					},
+			},
+			&QueuingStrategy::default(),
+		)
+		.unwrap();
+
+		let source = readable_from_stream(stream::iter(["a", "b", "c"]), &QueuingStrategy::default()).unwrap();
+		let reader = Reader::<JsValue>::new(&source).unwrap();
+		let mut writer = TypedWriter::<JsValue>::new(&destination).unwrap();
+
+		// Forwarding closes the sink once the stream ends.
+		reader.forward(&mut writer).await.unwrap();
+		writer.closed().await.unwrap();
+
+		assert_eq!(*written.borrow(), ["a", "b", "c"]);
+	}
+
+	#[cfg(feature = "tokio")]
+	#[wasm_bindgen_test]
+	async fn async_write() {
+		use tokio::io::AsyncWriteExt;
+
+		let written = Rc::new(RefCell::new(Vec::new()));
+		let stream = writable_from_fn(
+			{
+				let written = written.clone();
+				move |chunk: Uint8Array| {
+					written.borrow_mut().extend(chunk.to_vec());
+					let full = written.borrow().len() > 6;
+					async move {
+						match full {
+							true => Err("full"),
+							false => Ok(()),
+						}
+					}
 				}
-			} else {
-                Ready(Ok(())) // No pending write, nothing to flush
-            }
-        }
-
-        fn poll_shutdown(
-            self: Pin<&mut Self>,
-            _cx: &mut Context<'_>,
-        ) -> Poll<Result<()>> {
-            let (inner, _) = Self::project(self);
-            inner.close().ignore();
-            let p = inner.closed();
-            let mut js_future = JsFuture::from(p);
-            match Pin::new(&mut js_future).poll(_cx) {
-                Pending => Pending,
-                Ready(Ok(_)) => Ready(Ok(())),
-                Ready(Err(err)) => {
-                    let js_err_str = err.as_string().unwrap_or_else(|| "unknown error".to_string());
-                    Ready(Err(Error::new(Other, format!("js shutdown error: {}", js_err_str))))
-                },
-            }
-        }
-    }
-}
\ No newline at end of file
+			},
+			&QueuingStrategy::Count(2.0),
+		)
+		.unwrap();
+		let mut writer = TypedWriter::<Uint8Array>::new(&stream).unwrap();
+
+		writer.write_all(b"abc").await.unwrap();
+		writer.write_all(b"def").await.unwrap();
+		writer.flush().await.unwrap();
+		assert_eq!(*written.borrow(), b"abcdef");
+
+		// The sink error is returned by a later call, once the write that caused it completes.
+		writer.write_all(b"ghi").await.unwrap();
+		assert!(writer.flush().await.is_err());
+		assert!(writer.write_all(b"jkl").await.is_err());
+	}
+
+	#[wasm_bindgen_test]
+	async fn close_then_poll_close() {
+		let stream = writable_from_fn(
+			|_: Uint8Array| async { Ok::<_, Error>(()) },
+			&QueuingStrategy::default(),
+		)
+		.unwrap();
+		let mut writer = TypedWriter::<Uint8Array>::new(&stream).unwrap();
+
+		writer.write(&Uint8Array::new_with_length(4)).await.unwrap();
+		writer.close();
+
+		// Closing a second time would reject with a TypeError.
+		poll_fn(|cx| Pin::new(&mut writer).poll_close(cx)).await.unwrap();
+		writer.closed().await.unwrap();
+
+		assert!(matches!(
+			writer.write(&Uint8Array::new_with_length(4)).await,
+			Err(Error::Closed)
+		));
+	}
+}